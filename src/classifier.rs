@@ -0,0 +1,61 @@
+use crate::filter::MessageFilter;
+use crate::parser::{parse_timestamp, LineParser};
+use crate::theme::Theme;
+use chrono::NaiveDateTime;
+use std::sync::{Arc, RwLock};
+use tui::style::Style;
+
+pub const ALL_MESSAGES_INDEX: usize = 0;
+
+/// The classifier in use, shared with the threads indexing files read in place. It is
+/// replaced rather than changed whenever the tabs change, so entries classified with an
+/// older one can be told apart and classified again.
+pub type SharedClassifier = Arc<RwLock<Arc<Classifier>>>;
+
+/// Decides how a line is shown and which tabs it goes to, from the parser, the level styles
+/// of the theme and the filters of the tabs.
+#[derive(Default)]
+pub struct Classifier {
+    parser: LineParser,
+    theme: Theme,
+    message_filters: Vec<MessageFilter>,
+}
+
+impl Classifier {
+    pub fn new(parser: &LineParser, theme: &Theme, message_filters: &[MessageFilter]) -> Self {
+        Self {
+            parser: parser.clone(),
+            theme: theme.clone(),
+            message_filters: message_filters.to_vec(),
+        }
+    }
+
+    /// Styles a message and finds the tabs it goes to, the first matching filter giving its
+    /// style, along with the time it was logged at.
+    pub fn classify(
+        &self,
+        source_label: &str,
+        message: &str,
+    ) -> (Style, Option<NaiveDateTime>, Vec<usize>) {
+        let record = self.parser.parse(message);
+        let mut tabs = vec![ALL_MESSAGES_INDEX];
+        let mut style = self.theme.message_style(record.level);
+
+        for (index, message_filter) in self.message_filters.iter().enumerate() {
+            if message_filter.is_match(message, &record, source_label) {
+                if tabs.len() == 1 {
+                    style = message_filter.style;
+                }
+
+                tabs.push(index + 1);
+            }
+        }
+
+        let timestamp = record
+            .timestamp
+            .as_ref()
+            .and_then(|timestamp| parse_timestamp(timestamp));
+
+        (style, timestamp, tabs)
+    }
+}
@@ -0,0 +1,896 @@
+use crate::classifier::{Classifier, SharedClassifier};
+use crate::filter::MessageFilter;
+use crate::multiline::EntryRules;
+use crate::parser::LineParser;
+use crate::state::{search_pattern, SavedSearch};
+use crate::store::RetentionLimits;
+use crate::theme::{self, Theme};
+use clap::{Arg, ArgMatches};
+use failure::Error;
+use std::env;
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+use toml::Value;
+use tui::style::{Color, Style};
+
+pub const APP_NAME: &str = "log_watcher";
+const CONFIG_FILE_NAME: &str = "config.toml";
+const CONFIG_LOG_PATH_TOML_PROPERTY: &str = "log_path";
+const CONFIG_LOG_PATHS_TOML_PROPERTY: &str = "log_paths";
+const CONFIG_COMMANDS_TOML_PROPERTY: &str = "commands";
+const MESSAGE_FILTERS_TOML_PROPERTY: &str = "message_filters";
+const FILTERS_TOML_PROPERTY: &str = "filters";
+const FILTER_NAME_TOML_PROPERTY: &str = "name";
+const FILTER_PATTERN_TOML_PROPERTY: &str = "pattern";
+const THEME_TOML_PROPERTY: &str = "theme";
+const PARSER_TOML_PROPERTY: &str = "parser";
+const JSON_TOML_PROPERTY: &str = "json";
+const MULTILINE_TOML_PROPERTY: &str = "multiline";
+const SAVED_SEARCHES_TOML_PROPERTY: &str = "saved_searches";
+const QUERY_TOML_PROPERTY: &str = "query";
+const KEY_TOML_PROPERTY: &str = "key";
+const COLUMNS_TOML_PROPERTY: &str = "columns";
+const RETENTION_TOML_PROPERTY: &str = "retention";
+const MAX_LINES_TOML_PROPERTY: &str = "max_lines";
+const MAX_BYTES_TOML_PROPERTY: &str = "max_bytes";
+const SPILL_TO_DISK_TOML_PROPERTY: &str = "spill_to_disk";
+const FOLLOW_TOML_PROPERTY: &str = "follow";
+const ROTATED_TOML_PROPERTY: &str = "rotated";
+const STATIC_TOML_PROPERTY: &str = "static";
+const TAIL_TOML_PROPERTY: &str = "tail";
+const TAIL_BYTES_TOML_PROPERTY: &str = "tail_bytes";
+const TICK_RATE_TOML_PROPERTY: &str = "tick_rate_ms";
+
+pub struct Config {
+    pub path: Option<PathBuf>,
+    pub log_paths: Vec<String>,
+    pub commands: Vec<String>,
+    pub message_filters: Vec<MessageFilter>,
+    pub parser: LineParser,
+    pub entry_rules: EntryRules,
+    pub json_columns: Vec<String>,
+    pub saved_searches: Vec<SavedSearch>,
+    pub follow: bool,
+    pub rotated: bool,
+    pub is_static: bool,
+    pub tail_lines: Option<usize>,
+    pub tail_bytes: Option<u64>,
+    pub tick_rate: Option<Duration>,
+    pub theme: Theme,
+    pub retention: RetentionLimits,
+    pub classifier: SharedClassifier,
+}
+
+impl Config {
+    /// Builds the config from command line arguments layered over the config file given
+    /// with `--config`, or the first one found in the working directory or XDG config dirs.
+    pub fn load() -> Result<Config, Error> {
+        Config::from_args(env::args_os())
+    }
+
+    /// Like `load`, with the given arguments, the first being the program name.
+    pub fn from_args<I, T>(args: I) -> Result<Config, Error>
+    where
+        I: IntoIterator<Item = T>,
+        T: Into<OsString> + Clone,
+    {
+        let args = parse_args(args);
+
+        let config_path = match args.value_of("config") {
+            Some(path) => Some(PathBuf::from(path)),
+            None => discover_config_file(),
+        };
+
+        let config = match &config_path {
+            Some(path) => read_config_file(path)?,
+            None => Value::Table(Default::default()),
+        };
+        let config_name = match &config_path {
+            Some(path) => path.display().to_string(),
+            None => "command line".to_string(),
+        };
+
+        let mut log_path_patterns = vec![];
+
+        if let Some(log_path) = config.get(CONFIG_LOG_PATH_TOML_PROPERTY) {
+            match log_path.as_str() {
+                Some(log_path) => log_path_patterns.push(log_path.to_string()),
+                None => failure::bail!("{}: log_path must be a string", config_name),
+            }
+        }
+
+        log_path_patterns.extend(string_list(
+            &config,
+            CONFIG_LOG_PATHS_TOML_PROPERTY,
+            &config_name,
+        )?);
+
+        let mut commands = string_list(&config, CONFIG_COMMANDS_TOML_PROPERTY, &config_name)?;
+        let mut message_filters = vec![];
+
+        for (index, definition) in
+            string_list(&config, MESSAGE_FILTERS_TOML_PROPERTY, &config_name)?
+                .iter()
+                .enumerate()
+        {
+            let message_filter =
+                MessageFilter::new(definition, definition, default_filter_style(index));
+
+            message_filters.push(message_filter.map_err(|e| {
+                failure::format_err!(
+                    "{}: message_filters[{}] {:?} is invalid: {}",
+                    config_name,
+                    index,
+                    definition,
+                    e
+                )
+            })?);
+        }
+
+        message_filters.extend(filter_tables(&config, message_filters.len(), &config_name)?);
+
+        let mut follow = match config.get(FOLLOW_TOML_PROPERTY) {
+            Some(follow) => follow.as_bool().ok_or_else(|| {
+                failure::format_err!("{}: follow must be true or false", config_name)
+            })?,
+            None => true,
+        };
+
+        let mut rotated = match config.get(ROTATED_TOML_PROPERTY) {
+            Some(rotated) => rotated.as_bool().ok_or_else(|| {
+                failure::format_err!("{}: rotated must be true or false", config_name)
+            })?,
+            None => false,
+        };
+
+        let mut is_static = match config.get(STATIC_TOML_PROPERTY) {
+            Some(is_static) => is_static.as_bool().ok_or_else(|| {
+                failure::format_err!("{}: static must be true or false", config_name)
+            })?,
+            None => false,
+        };
+
+        let mut tail_lines = match config.get(TAIL_TOML_PROPERTY) {
+            Some(tail) => match tail.as_integer() {
+                Some(tail) if tail >= 0 => Some(tail as usize),
+                _ => failure::bail!(
+                    "{}: tail must be a non-negative number of lines",
+                    config_name
+                ),
+            },
+            None => None,
+        };
+
+        let mut tail_bytes = match config.get(TAIL_BYTES_TOML_PROPERTY) {
+            Some(_) if tail_lines.is_some() => {
+                failure::bail!("{}: tail and tail_bytes cannot both be set", config_name)
+            }
+            Some(tail_bytes) => match tail_bytes.as_integer() {
+                Some(tail_bytes) if tail_bytes >= 0 => Some(tail_bytes as u64),
+                _ => failure::bail!(
+                    "{}: tail_bytes must be a non-negative number of bytes",
+                    config_name
+                ),
+            },
+            None => None,
+        };
+
+        let mut tick_rate = match config.get(TICK_RATE_TOML_PROPERTY) {
+            Some(tick_rate) => match tick_rate.as_integer() {
+                Some(tick_rate) if tick_rate > 0 => Some(Duration::from_millis(tick_rate as u64)),
+                _ => failure::bail!(
+                    "{}: tick_rate_ms must be a positive number of milliseconds",
+                    config_name
+                ),
+            },
+            None => None,
+        };
+
+        if let Some(log_paths) = args.values_of("log_paths") {
+            log_path_patterns = log_paths.map(str::to_string).collect();
+            commands.clear();
+        }
+
+        if let Some(arg_commands) = args.values_of("command") {
+            if args.values_of("log_paths").is_none() {
+                log_path_patterns.clear();
+            }
+
+            commands = arg_commands.map(str::to_string).collect();
+        }
+
+        if let Some(filters) = args.values_of("filter") {
+            message_filters = filters
+                .enumerate()
+                .map(|(index, definition)| {
+                    MessageFilter::new(definition, definition, default_filter_style(index)).map_err(
+                        |e| failure::format_err!("--filter {:?} is invalid: {}", definition, e),
+                    )
+                })
+                .collect::<Result<_, _>>()?;
+        }
+
+        let (saved_searches, saved_search_filters) =
+            saved_searches(&config, message_filters.len(), &config_name)?;
+
+        message_filters.extend(saved_search_filters);
+
+        if args.is_present("no-follow") {
+            follow = false;
+        }
+
+        if args.is_present("rotated") {
+            rotated = true;
+        }
+
+        if args.is_present("static") {
+            is_static = true;
+        }
+
+        if args.is_present("from-start") {
+            tail_lines = None;
+            tail_bytes = None;
+        }
+
+        if let Some(tail) = args.value_of("tail") {
+            tail_lines = Some(tail.parse().map_err(|_| {
+                failure::format_err!("--tail expects a number of lines, got {}", tail)
+            })?);
+            tail_bytes = None;
+        }
+
+        if let Some(arg_tail_bytes) = args.value_of("tail-bytes") {
+            tail_bytes = Some(arg_tail_bytes.parse().map_err(|_| {
+                failure::format_err!(
+                    "--tail-bytes expects a number of bytes, got {}",
+                    arg_tail_bytes
+                )
+            })?);
+            tail_lines = None;
+        }
+
+        // Checked once the arguments are layered over the file, as each of these can be set
+        // in either of them
+        if is_static && (rotated || tail_lines.is_some() || tail_bytes.is_some()) {
+            failure::bail!(
+                "static cannot be combined with rotated, tail or tail_bytes, \
+                 --from-start drops a tail set in {}",
+                config_name
+            );
+        }
+
+        if rotated && (tail_lines.is_some() || tail_bytes.is_some()) {
+            failure::bail!(
+                "rotated files are read from the oldest one and cannot be combined with tail or \
+                 tail_bytes, --from-start drops a tail set in {}",
+                config_name
+            );
+        }
+
+        if let Some(arg_tick_rate) = args.value_of("tick-rate") {
+            tick_rate = match arg_tick_rate.parse() {
+                Ok(tick_rate) if tick_rate > 0 => Some(Duration::from_millis(tick_rate)),
+                _ => failure::bail!(
+                    "--tick-rate expects a positive number of milliseconds, got {}",
+                    arg_tick_rate
+                ),
+            };
+        }
+
+        if log_path_patterns.is_empty() && commands.is_empty() {
+            failure::bail!(
+                "No log sources given, pass a log path or set log_paths in {}",
+                match &config_path {
+                    Some(path) => path.display().to_string(),
+                    None => format!("~/.config/{}/{}", APP_NAME, CONFIG_FILE_NAME),
+                }
+            );
+        }
+
+        let json_columns = match config.get(JSON_TOML_PROPERTY) {
+            Some(json) if json.is_table() => {
+                string_list(json, COLUMNS_TOML_PROPERTY, &config_name)?
+            }
+            Some(_) => failure::bail!("{}: json must be a table", config_name),
+            None => vec![],
+        };
+
+        // A [json] section on its own switches to JSON-lines parsing
+        let parser = match args.value_of("format") {
+            Some(format) => {
+                LineParser::preset(format).map_err(|e| failure::format_err!("--format: {}", e))?
+            }
+            None if config.get(PARSER_TOML_PROPERTY).is_none()
+                && config.get(JSON_TOML_PROPERTY).is_some() =>
+            {
+                LineParser::Json
+            }
+            None => LineParser::new(config.get(PARSER_TOML_PROPERTY))
+                .map_err(|e| failure::format_err!("{}: {}", config_name, e))?,
+        };
+
+        let entry_rules = EntryRules::new(config.get(MULTILINE_TOML_PROPERTY))
+            .map_err(|e| failure::format_err!("{}: {}", config_name, e))?;
+
+        let theme = Theme::new(config.get(THEME_TOML_PROPERTY))
+            .map_err(|e| failure::format_err!("{}: {}", config_name, e))?;
+
+        let retention = retention_limits(config.get(RETENTION_TOML_PROPERTY))
+            .map_err(|e| failure::format_err!("{}: {}", config_name, e))?;
+
+        let classifier = Classifier::new(&parser, &theme, &message_filters);
+
+        Ok(Config {
+            path: config_path,
+            log_paths: expand_log_paths(&log_path_patterns)?,
+            commands,
+            message_filters,
+            parser,
+            entry_rules,
+            json_columns,
+            saved_searches,
+            follow,
+            rotated,
+            is_static,
+            tail_lines,
+            tail_bytes,
+            tick_rate,
+            theme,
+            retention,
+            classifier: Arc::new(RwLock::new(Arc::new(classifier))),
+        })
+    }
+
+    /// Shares a new classifier after the filters were added to, removed or reordered.
+    pub fn update_classifier(&self) {
+        let classifier = Classifier::new(&self.parser, &self.theme, &self.message_filters);
+
+        *self.classifier.write().unwrap() = Arc::new(classifier);
+    }
+
+    /// Appends a filter as a `[[filters]]` table to the config file in use, or to a new
+    /// one in the XDG config dir when there is none, and tells where it was written. The
+    /// file is appended to rather than rewritten so its comments and layout are kept.
+    pub fn append_filter(&mut self, name: &str, pattern: &str) -> Result<PathBuf, Error> {
+        let path = match &self.path {
+            Some(path) => path.clone(),
+            None => default_config_path()
+                .ok_or_else(|| failure::format_err!("No config file and HOME is not set"))?,
+        };
+
+        let mut table = toml::value::Table::new();
+        table.insert(
+            FILTER_NAME_TOML_PROPERTY.to_string(),
+            Value::String(name.to_string()),
+        );
+        table.insert(
+            FILTER_PATTERN_TOML_PROPERTY.to_string(),
+            Value::String(pattern.to_string()),
+        );
+
+        let mut content = std::fs::read_to_string(&path).unwrap_or_default();
+
+        if !content.is_empty() && !content.ends_with('\n') {
+            content.push('\n');
+        }
+
+        content.push_str(&format!(
+            "\n[[{}]]\n{}",
+            FILTERS_TOML_PROPERTY,
+            toml::to_string(&Value::Table(table))?
+        ));
+
+        // A table can only be appended to filters declared as tables, not to an inline
+        // `filters = [...]` array, which the appended text would define a second time
+        if content.parse::<Value>().is_err() {
+            failure::bail!(
+                "{} declares {} inline, add the filter to it by hand",
+                path.display(),
+                FILTERS_TOML_PROPERTY
+            );
+        }
+
+        if let Some(directory) = path.parent() {
+            std::fs::create_dir_all(directory)?;
+        }
+
+        std::fs::write(&path, content).map_err(|e| {
+            failure::format_err!("Failed writing config file {}: {}", path.display(), e)
+        })?;
+
+        self.path = Some(path.clone());
+
+        Ok(path)
+    }
+}
+
+fn parse_args<'a, I, T>(args: I) -> ArgMatches<'a>
+where
+    I: IntoIterator<Item = T>,
+    T: Into<OsString> + Clone,
+{
+    clap::App::new(APP_NAME)
+        .version(env!("CARGO_PKG_VERSION"))
+        .about("Watch log files in real time")
+        .arg(
+            Arg::with_name("log_paths")
+                .value_name("LOG_PATH")
+                .help("Log files or glob patterns to watch, - reads from stdin")
+                .multiple(true),
+        )
+        .arg(
+            Arg::with_name("config")
+                .long("config")
+                .short("c")
+                .value_name("PATH")
+                .help("Config file to use instead of the discovered config.toml")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("command")
+                .long("command")
+                .value_name("COMMAND")
+                .help("Shell command whose output is watched, can be repeated")
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1),
+        )
+        .arg(
+            Arg::with_name("filter")
+                .long("filter")
+                .short("f")
+                .value_name("FILTER")
+                .help("Message filter shown as a tab, can be repeated, replaces message_filters")
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1),
+        )
+        .arg(
+            Arg::with_name("no-follow")
+                .long("no-follow")
+                .help("Read log files once instead of following them for new lines"),
+        )
+        .arg(
+            Arg::with_name("rotated")
+                .long("rotated")
+                .help("Read the rotated copies of each log file, oldest first, before it")
+                .conflicts_with_all(&["tail", "tail-bytes"]),
+        )
+        .arg(
+            Arg::with_name("static")
+                .long("static")
+                .help("Read log files in place from the first line without following them, the files must not be truncated while open")
+                .conflicts_with_all(&["rotated", "tail", "tail-bytes"]),
+        )
+        .arg(
+            Arg::with_name("from-start")
+                .long("from-start")
+                .help("Read log files from the first line, overrides tail from the config")
+                .conflicts_with_all(&["tail", "tail-bytes"]),
+        )
+        .arg(
+            Arg::with_name("tail")
+                .long("tail")
+                .short("n")
+                .value_name("N")
+                .help("Start from the last N lines of each log file, compressed files are read from the start")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("tail-bytes")
+                .long("tail-bytes")
+                .value_name("N")
+                .help("Start from the lines in the last N bytes of each log file, compressed files are read from the start")
+                .takes_value(true)
+                .conflicts_with("tail"),
+        )
+        .arg(
+            Arg::with_name("format")
+                .long("format")
+                .value_name("FORMAT")
+                .help("Line format: plain, logback, log4j, syslog, nginx or json")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("tick-rate")
+                .long("tick-rate")
+                .value_name("MS")
+                .help("Refresh interval, used for polling where file changes cannot be watched")
+                .takes_value(true),
+        )
+        .get_matches_from(args)
+}
+
+fn discover_config_file() -> Option<PathBuf> {
+    let mut candidates = vec![PathBuf::from(CONFIG_FILE_NAME)];
+
+    if let Some(config_home) = env::var_os("XDG_CONFIG_HOME") {
+        candidates.push(
+            PathBuf::from(config_home)
+                .join(APP_NAME)
+                .join(CONFIG_FILE_NAME),
+        );
+    }
+
+    if let Some(home) = env::var_os("HOME") {
+        candidates.push(
+            PathBuf::from(home)
+                .join(".config")
+                .join(APP_NAME)
+                .join(CONFIG_FILE_NAME),
+        );
+    }
+
+    candidates.into_iter().find(|candidate| candidate.is_file())
+}
+
+fn default_config_path() -> Option<PathBuf> {
+    let config_home = match env::var_os("XDG_CONFIG_HOME") {
+        Some(config_home) => PathBuf::from(config_home),
+        None => PathBuf::from(env::var_os("HOME")?).join(".config"),
+    };
+
+    Some(config_home.join(APP_NAME).join(CONFIG_FILE_NAME))
+}
+
+fn read_config_file(path: &Path) -> Result<Value, Error> {
+    let content = std::fs::read_to_string(path).map_err(|e| {
+        failure::format_err!("Failed reading config file {}: {}", path.display(), e)
+    })?;
+
+    content
+        .parse::<Value>()
+        .map_err(|e| failure::format_err!("Invalid config file {}: {}", path.display(), e))
+}
+
+fn string_list(config: &Value, property: &str, config_name: &str) -> Result<Vec<String>, Error> {
+    match config.get(property) {
+        Some(value) => value.clone().try_into::<Vec<String>>().map_err(|_| {
+            failure::format_err!("{}: {} must be a list of strings", config_name, property)
+        }),
+        None => Ok(vec![]),
+    }
+}
+
+/// Reads `[[saved_searches]]` tables, each with a `name`, a `query`, the `regex`,
+/// `case_sensitive` and `whole_word` modes, an optional `key` and `tab = true` to also
+/// show the matches in a tab of their own.
+fn saved_searches(
+    config: &Value,
+    first_index: usize,
+    config_name: &str,
+) -> Result<(Vec<SavedSearch>, Vec<MessageFilter>), Error> {
+    let tables = match config.get(SAVED_SEARCHES_TOML_PROPERTY) {
+        Some(Value::Array(tables)) => tables,
+        Some(_) => failure::bail!("{}: saved_searches must be an array of tables", config_name),
+        None => return Ok((vec![], vec![])),
+    };
+
+    let mut saved_searches = vec![];
+    let mut message_filters = vec![];
+
+    for (index, table) in tables.iter().enumerate() {
+        let error = |message: &str| {
+            failure::format_err!("{}: saved_searches[{}] {}", config_name, index, message)
+        };
+        let string = |property: &str| match table.get(property) {
+            Some(value) => value
+                .as_str()
+                .map(|value| Some(value.to_string()))
+                .ok_or_else(|| error(&format!("{} must be a string", property))),
+            None => Ok(None),
+        };
+        let flag = |property: &str| match table.get(property) {
+            Some(value) => value
+                .as_bool()
+                .ok_or_else(|| error(&format!("{} must be true or false", property))),
+            None => Ok(false),
+        };
+
+        let query = string(QUERY_TOML_PROPERTY)?.ok_or_else(|| error("is missing query"))?;
+        let key = match string(KEY_TOML_PROPERTY)? {
+            Some(key) if key.chars().count() == 1 => key.chars().next(),
+            Some(_) => return Err(error("key must be a single character")),
+            None => None,
+        };
+
+        let saved_search = SavedSearch {
+            name: string(FILTER_NAME_TOML_PROPERTY)?.unwrap_or_else(|| query.clone()),
+            query,
+            is_regex: flag("regex")?,
+            is_case_sensitive: flag("case_sensitive")?,
+            is_whole_word: flag("whole_word")?,
+            key,
+        };
+
+        if flag("tab")? {
+            let pattern = search_pattern(
+                &saved_search.query,
+                saved_search.is_regex,
+                saved_search.is_case_sensitive,
+                saved_search.is_whole_word,
+            );
+            let style = default_filter_style(first_index + message_filters.len());
+
+            message_filters.push(
+                MessageFilter::new(&saved_search.name, &format!("re:{}", pattern), style)
+                    .map_err(|e| error(&format!("is invalid: {}", e)))?,
+            );
+        }
+
+        saved_searches.push(saved_search);
+    }
+
+    Ok((saved_searches, message_filters))
+}
+
+/// Reads `[[filters]]` tables, each with a `name`, a `pattern` in the same syntax as
+/// `message_filters` entries and optional style keys.
+fn filter_tables(
+    config: &Value,
+    first_index: usize,
+    config_name: &str,
+) -> Result<Vec<MessageFilter>, Error> {
+    let tables = match config.get(FILTERS_TOML_PROPERTY) {
+        Some(Value::Array(tables)) => tables,
+        Some(_) => failure::bail!("{}: filters must be an array of tables", config_name),
+        None => return Ok(vec![]),
+    };
+
+    let mut message_filters = vec![];
+
+    for (index, table) in tables.iter().enumerate() {
+        let pattern = table
+            .get(FILTER_PATTERN_TOML_PROPERTY)
+            .and_then(Value::as_str)
+            .ok_or_else(|| {
+                failure::format_err!("{}: filters[{}] is missing pattern", config_name, index)
+            })?;
+        let name = match table.get(FILTER_NAME_TOML_PROPERTY) {
+            Some(name) => name.as_str().ok_or_else(|| {
+                failure::format_err!("{}: filters[{}].name must be a string", config_name, index)
+            })?,
+            None => pattern,
+        };
+
+        let message_filter = theme::parse_style(table, default_filter_style(first_index + index))
+            .and_then(|style| MessageFilter::new(name, pattern, style))
+            .map_err(|e| {
+                failure::format_err!(
+                    "{}: filters[{}] {:?} is invalid: {}",
+                    config_name,
+                    index,
+                    name,
+                    e
+                )
+            })?;
+
+        message_filters.push(message_filter);
+    }
+
+    Ok(message_filters)
+}
+
+/// Reads the `[retention]` table, limits that are left out are unbounded.
+fn retention_limits(retention: Option<&Value>) -> Result<RetentionLimits, Error> {
+    let retention = match retention {
+        Some(retention) if retention.is_table() => retention,
+        Some(_) => failure::bail!("retention must be a table"),
+        None => return Ok(RetentionLimits::default()),
+    };
+
+    let limit = |property: &str| match retention.get(property) {
+        Some(value) => match value.as_integer() {
+            Some(limit) if limit > 0 => Ok(Some(limit as usize)),
+            _ => Err(failure::format_err!(
+                "retention.{} must be a positive number",
+                property
+            )),
+        },
+        None => Ok(None),
+    };
+
+    let spill_to_disk = match retention.get(SPILL_TO_DISK_TOML_PROPERTY) {
+        Some(value) => value
+            .as_bool()
+            .ok_or_else(|| failure::format_err!("retention.spill_to_disk must be true or false"))?,
+        None => false,
+    };
+
+    Ok(RetentionLimits {
+        max_lines: limit(MAX_LINES_TOML_PROPERTY)?,
+        max_bytes: limit(MAX_BYTES_TOML_PROPERTY)?,
+        spill_to_disk,
+    })
+}
+
+pub fn default_filter_style(index: usize) -> Style {
+    Style::default().fg(Color::Indexed((index + 1) as u8))
+}
+
+fn expand_log_paths(patterns: &[String]) -> Result<Vec<String>, Error> {
+    let mut log_paths: Vec<String> = vec![];
+
+    for pattern in patterns {
+        if !pattern.contains(&['*', '?', '['][..]) {
+            log_paths.push(pattern.clone());
+            continue;
+        }
+
+        let mut matches: Vec<String> = glob::glob(pattern)
+            .map_err(|e| failure::format_err!("Invalid log path pattern {}: {}", pattern, e))?
+            .filter_map(Result::ok)
+            .map(|path| path.to_string_lossy().to_string())
+            .collect();
+
+        if matches.is_empty() {
+            failure::bail!("Log path pattern {} did not match any files", pattern);
+        }
+
+        matches.sort();
+        log_paths.extend(matches);
+    }
+
+    let mut unique_log_paths = vec![];
+
+    for log_path in log_paths {
+        if !unique_log_paths.contains(&log_path) {
+            unique_log_paths.push(log_path);
+        }
+    }
+
+    Ok(unique_log_paths)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::TempDir;
+
+    fn config_file(content: &str) -> (TempDir, String) {
+        let directory = tempfile::tempdir().unwrap();
+        let path = directory.path().join(CONFIG_FILE_NAME);
+        fs::write(&path, content).unwrap();
+
+        (directory, path.display().to_string())
+    }
+
+    fn load(content: &str, args: &[&str]) -> Result<Config, String> {
+        let (_directory, path) = config_file(content);
+        let mut all_args = vec![APP_NAME, "--config", &path];
+        all_args.extend(args);
+
+        Config::from_args(all_args).map_err(|e| e.to_string().replace(&path, "CONFIG"))
+    }
+
+    fn filter_names(config: &Config) -> Vec<&str> {
+        config
+            .message_filters
+            .iter()
+            .map(|filter| filter.name.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn the_config_file_is_used_without_arguments() {
+        let config = load(
+            "log_path = \"a.log\"\nlog_paths = [\"b.log\", \"a.log\"]\ncommands = [\"dmesg\"]\n\
+             message_filters = [\"error\"]\nfollow = false\ntail = 20\ntick_rate_ms = 50",
+            &[],
+        )
+        .unwrap();
+
+        assert_eq!(config.log_paths, vec!["a.log", "b.log"]);
+        assert_eq!(config.commands, vec!["dmesg"]);
+        assert_eq!(filter_names(&config), vec!["error"]);
+        assert!(!config.follow);
+        assert_eq!(config.tail_lines, Some(20));
+        assert_eq!(config.tick_rate, Some(Duration::from_millis(50)));
+        assert!(config.parser.is_plain());
+    }
+
+    #[test]
+    fn arguments_override_the_config_file() {
+        let content =
+            "log_paths = [\"a.log\"]\ncommands = [\"dmesg\"]\nmessage_filters = [\"error\"]\n\
+                       tail_bytes = 100\n[parser]\nformat = \"syslog\"";
+
+        let config = load(
+            content,
+            &[
+                "c.log",
+                "-f",
+                "warn",
+                "-f",
+                "info",
+                "-n",
+                "5",
+                "--format",
+                "json",
+                "--no-follow",
+            ],
+        )
+        .unwrap();
+        assert_eq!(config.log_paths, vec!["c.log"]);
+        assert!(config.commands.is_empty());
+        assert_eq!(filter_names(&config), vec!["warn", "info"]);
+        assert_eq!((config.tail_lines, config.tail_bytes), (Some(5), None));
+        assert!(matches!(config.parser, LineParser::Json));
+        assert!(!config.follow);
+
+        let config = load(content, &["--command", "journalctl -f", "--from-start"]).unwrap();
+        assert!(config.log_paths.is_empty());
+        assert_eq!(config.commands, vec!["journalctl -f"]);
+        assert_eq!((config.tail_lines, config.tail_bytes), (None, None));
+        assert!(matches!(config.parser, LineParser::Regex(_)));
+    }
+
+    #[test]
+    fn invalid_values_name_where_they_come_from() {
+        let error = |content: &str, args: &[&str]| load(content, args).err().unwrap();
+
+        assert_eq!(
+            error("follow = 1", &["a.log"]),
+            "CONFIG: follow must be true or false"
+        );
+        assert_eq!(
+            error("tail = -1", &["a.log"]),
+            "CONFIG: tail must be a non-negative number of lines"
+        );
+        assert_eq!(
+            error("tail = 1\ntail_bytes = 1", &["a.log"]),
+            "CONFIG: tail and tail_bytes cannot both be set"
+        );
+        assert_eq!(
+            error("", &["a.log", "--tail", "ten"]),
+            "--tail expects a number of lines, got ten"
+        );
+        assert_eq!(
+            error("", &["a.log", "--tick-rate", "0"]),
+            "--tick-rate expects a positive number of milliseconds, got 0"
+        );
+        assert!(
+            error("", &["a.log", "-f", "expr:("]).starts_with("--filter \"expr:(\" is invalid: ")
+        );
+        assert_eq!(
+            error("log_paths = []", &[]),
+            "No log sources given, pass a log path or set log_paths in CONFIG"
+        );
+    }
+
+    #[test]
+    fn static_and_rotated_reading_refuse_a_tail_from_either_layer() {
+        let is_refused = |content: &str, args: &[&str], mode: &str| {
+            load(content, args)
+                .err()
+                .is_some_and(|error| error.starts_with(mode))
+        };
+
+        assert!(is_refused("static = true\ntail = 10", &["a.log"], "static"));
+        assert!(is_refused(
+            "tail_bytes = 10",
+            &["a.log", "--static"],
+            "static"
+        ));
+        assert!(is_refused(
+            "rotated = true",
+            &["a.log", "--static"],
+            "static"
+        ));
+        assert!(is_refused(
+            "rotated = true",
+            &["a.log", "-n", "5"],
+            "rotated"
+        ));
+        assert!(is_refused("tail = 10", &["a.log", "--rotated"], "rotated"));
+
+        let config = load("tail = 10\nrotated = true", &["a.log", "--from-start"]).unwrap();
+        assert!(config.rotated && config.tail_lines.is_none());
+
+        let config = load("tail = 10", &["a.log", "--static", "--from-start"]).unwrap();
+        assert!(config.is_static && config.tail_lines.is_none());
+    }
+}
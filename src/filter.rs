@@ -0,0 +1,372 @@
+use crate::parser::{Level, LogRecord};
+use failure::Error;
+use regex::Regex;
+use tui::style::Style;
+
+const REGEX_PREFIX: &str = "re:";
+const EXPRESSION_PREFIX: &str = "expr:";
+const FIELD_PREFIX: &str = "field.";
+
+/// A message filter from config.toml. Plain patterns match as substrings, patterns prefixed
+/// with `re:` as regular expressions and patterns prefixed with `expr:` as boolean
+/// expressions such as `level:ERR and not text:"healthcheck"`.
+#[derive(Clone)]
+pub struct MessageFilter {
+    pub name: String,
+    pub pattern: String,
+    pub style: Style,
+    matcher: Matcher,
+}
+
+#[derive(Clone)]
+enum Matcher {
+    Contains(String),
+    Regex(Regex),
+    Expression(Expression),
+}
+
+#[derive(Clone)]
+enum Expression {
+    And(Box<Expression>, Box<Expression>),
+    Or(Box<Expression>, Box<Expression>),
+    Not(Box<Expression>),
+    Text(String),
+    Regex(Regex),
+    Level(Level),
+    Logger(String),
+    Field(String, String),
+    Source(String),
+}
+
+#[derive(PartialEq)]
+enum Token {
+    OpeningParenthesis,
+    ClosingParenthesis,
+    And,
+    Or,
+    Not,
+    Term(String, String),
+}
+
+struct Parser {
+    tokens: Vec<(usize, Token)>,
+    position: usize,
+    end_column: usize,
+}
+
+impl MessageFilter {
+    pub fn new(name: &str, pattern: &str, style: Style) -> Result<MessageFilter, Error> {
+        let matcher = if let Some(regex) = pattern.strip_prefix(REGEX_PREFIX) {
+            Matcher::Regex(Regex::new(regex)?)
+        } else if let Some(expression) = pattern.strip_prefix(EXPRESSION_PREFIX) {
+            Matcher::Expression(Parser::parse(expression, EXPRESSION_PREFIX.len())?)
+        } else {
+            Matcher::Contains(pattern.to_string())
+        };
+
+        Ok(MessageFilter {
+            name: name.to_string(),
+            pattern: pattern.to_string(),
+            style,
+            matcher,
+        })
+    }
+
+    /// Text and regex terms match the whole line, level and logger terms the parsed record.
+    pub fn is_match(&self, message: &str, record: &LogRecord, source: &str) -> bool {
+        match &self.matcher {
+            Matcher::Contains(text) => message.contains(text.as_str()),
+            Matcher::Regex(regex) => regex.is_match(message),
+            Matcher::Expression(expression) => expression.is_match(message, record, source),
+        }
+    }
+}
+
+impl Expression {
+    fn is_match(&self, message: &str, record: &LogRecord, source: &str) -> bool {
+        match self {
+            Expression::And(left, right) => {
+                left.is_match(message, record, source) && right.is_match(message, record, source)
+            }
+            Expression::Or(left, right) => {
+                left.is_match(message, record, source) || right.is_match(message, record, source)
+            }
+            Expression::Not(expression) => !expression.is_match(message, record, source),
+            Expression::Text(text) => message.contains(text.as_str()),
+            Expression::Regex(regex) => regex.is_match(message),
+            Expression::Level(level) => record.level == Some(*level),
+            Expression::Logger(name) => match &record.logger {
+                Some(logger) => logger.contains(name.as_str()),
+                None => false,
+            },
+            Expression::Field(name, value) => match record.field(name) {
+                Some(field) => field.contains(value.as_str()),
+                None => false,
+            },
+            Expression::Source(name) => source.contains(name.as_str()),
+        }
+    }
+}
+
+impl Parser {
+    /// Parses `input`, reporting error columns shifted by `column_offset` so they point
+    /// into the whole filter entry rather than just the expression part.
+    fn parse(input: &str, column_offset: usize) -> Result<Expression, Error> {
+        let mut parser = Parser {
+            tokens: tokenize(input, column_offset)?,
+            position: 0,
+            end_column: column_offset + input.chars().count() + 1,
+        };
+
+        let expression = parser.parse_or()?;
+
+        if let Some((column, _)) = parser.tokens.get(parser.position) {
+            failure::bail!("unexpected input at column {}", column);
+        }
+
+        Ok(expression)
+    }
+
+    fn parse_or(&mut self) -> Result<Expression, Error> {
+        let mut expression = self.parse_and()?;
+
+        while self.next_is(&Token::Or) {
+            self.position += 1;
+            expression = Expression::Or(Box::new(expression), Box::new(self.parse_and()?));
+        }
+
+        Ok(expression)
+    }
+
+    fn parse_and(&mut self) -> Result<Expression, Error> {
+        let mut expression = self.parse_unary()?;
+
+        while self.next_is(&Token::And) {
+            self.position += 1;
+            expression = Expression::And(Box::new(expression), Box::new(self.parse_unary()?));
+        }
+
+        Ok(expression)
+    }
+
+    fn parse_unary(&mut self) -> Result<Expression, Error> {
+        if self.next_is(&Token::Not) {
+            self.position += 1;
+
+            return Ok(Expression::Not(Box::new(self.parse_unary()?)));
+        }
+
+        self.parse_primary()
+    }
+
+    fn parse_primary(&mut self) -> Result<Expression, Error> {
+        let (column, token) = match self.tokens.get(self.position) {
+            Some((column, token)) => (*column, token),
+            None => failure::bail!("expected a term at column {}", self.end_column),
+        };
+
+        self.position += 1;
+
+        match token {
+            Token::OpeningParenthesis => {
+                let expression = self.parse_or()?;
+
+                if !self.next_is(&Token::ClosingParenthesis) {
+                    failure::bail!("missing ) for ( at column {}", column);
+                }
+
+                self.position += 1;
+
+                Ok(expression)
+            }
+            Token::Term(field, value) => match field.as_str() {
+                "text" => Ok(Expression::Text(value.clone())),
+                "re" => Ok(Expression::Regex(Regex::new(value).map_err(|e| {
+                    failure::format_err!("invalid regex at column {}: {}", column, e)
+                })?)),
+                "level" => match Level::parse(value) {
+                    Some(level) => Ok(Expression::Level(level)),
+                    None => failure::bail!("unknown level {} at column {}", value, column),
+                },
+                "logger" => Ok(Expression::Logger(value.clone())),
+                "source" => Ok(Expression::Source(value.clone())),
+                field => match field.strip_prefix(FIELD_PREFIX) {
+                    Some(name) if !name.is_empty() => {
+                        Ok(Expression::Field(name.to_string(), value.clone()))
+                    }
+                    _ => failure::bail!(
+                        "unknown field {} at column {}, expected text, re, level, logger, \
+                         source or field.<name>",
+                        field,
+                        column
+                    ),
+                },
+            },
+            _ => failure::bail!("expected a term at column {}", column),
+        }
+    }
+
+    fn next_is(&self, token: &Token) -> bool {
+        match self.tokens.get(self.position) {
+            Some((_, next)) => next == token,
+            None => false,
+        }
+    }
+}
+
+fn tokenize(input: &str, column_offset: usize) -> Result<Vec<(usize, Token)>, Error> {
+    let chars: Vec<char> = input.chars().collect();
+    let mut tokens = vec![];
+    let mut index = 0;
+
+    while index < chars.len() {
+        let column = column_offset + index + 1;
+
+        match chars[index] {
+            c if c.is_whitespace() => index += 1,
+            '(' => {
+                tokens.push((column, Token::OpeningParenthesis));
+                index += 1;
+            }
+            ')' => {
+                tokens.push((column, Token::ClosingParenthesis));
+                index += 1;
+            }
+            '"' => {
+                let (value, next_index) = read_quoted(&chars, index, column_offset)?;
+                tokens.push((column, Token::Term("text".to_string(), value)));
+                index = next_index;
+            }
+            _ => {
+                let start = index;
+
+                while index < chars.len()
+                    && !chars[index].is_whitespace()
+                    && !['(', ')', '"'].contains(&chars[index])
+                {
+                    index += 1;
+                }
+
+                let word: String = chars[start..index].iter().collect();
+
+                let token = match word.find(':') {
+                    Some(separator) if word.ends_with(':') && chars.get(index) == Some(&'"') => {
+                        let (value, next_index) = read_quoted(&chars, index, column_offset)?;
+                        index = next_index;
+
+                        Token::Term(word[..separator].to_string(), value)
+                    }
+                    Some(separator) if separator + 1 < word.len() => Token::Term(
+                        word[..separator].to_string(),
+                        word[separator + 1..].to_string(),
+                    ),
+                    Some(_) => failure::bail!("missing value for {} at column {}", word, column),
+                    None if word.eq_ignore_ascii_case("and") => Token::And,
+                    None if word.eq_ignore_ascii_case("or") => Token::Or,
+                    None if word.eq_ignore_ascii_case("not") => Token::Not,
+                    None => Token::Term("text".to_string(), word),
+                };
+
+                tokens.push((column, token));
+            }
+        }
+    }
+
+    Ok(tokens)
+}
+
+fn read_quoted(
+    chars: &[char],
+    opening_quote_index: usize,
+    column_offset: usize,
+) -> Result<(String, usize), Error> {
+    let mut value = String::new();
+    let mut index = opening_quote_index + 1;
+
+    while index < chars.len() {
+        match chars[index] {
+            '\\' if index + 1 < chars.len() => {
+                value.push(chars[index + 1]);
+                index += 2;
+            }
+            '"' => return Ok((value, index + 1)),
+            c => {
+                value.push(c);
+                index += 1;
+            }
+        }
+    }
+
+    failure::bail!(
+        "unterminated quote starting at column {}",
+        column_offset + opening_quote_index + 1
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn matches(pattern: &str, message: &str) -> bool {
+        MessageFilter::new("test", pattern, Style::default())
+            .unwrap()
+            .is_match(message, &LogRecord::plain(message), "app.log")
+    }
+
+    fn error(pattern: &str) -> String {
+        match MessageFilter::new("test", pattern, Style::default()) {
+            Ok(_) => panic!("{} should not parse", pattern),
+            Err(error) => error.to_string(),
+        }
+    }
+
+    #[test]
+    fn and_binds_tighter_than_or() {
+        assert!(matches("expr:a or b and c", "a"));
+        assert!(matches("expr:a or b and c", "b c"));
+        assert!(!matches("expr:a or b and c", "b"));
+        assert!(!matches("expr:(a or b) and c", "a"));
+        assert!(matches("expr:(a or b) and c", "a c"));
+    }
+
+    #[test]
+    fn not_applies_to_the_next_term_only() {
+        assert!(matches("expr:not a and b", "b"));
+        assert!(!matches("expr:not a and b", "a b"));
+        assert!(matches("expr:not (a and b)", "a"));
+        assert!(matches("expr:NOT a OR a", "a"));
+    }
+
+    #[test]
+    fn terms_match_their_part_of_the_record() {
+        let record = LogRecord {
+            timestamp: None,
+            level: Some(Level::Error),
+            logger: Some("com.app.Db".to_string()),
+            message: "timeout".to_string(),
+            fields: vec![("trace_id".to_string(), "4bf92f35".to_string())],
+        };
+        let filter = MessageFilter::new(
+            "test",
+            r#"expr:level:ERR and logger:app.Db and field.trace_id:4bf9 and text:"time out" or source:app"#,
+            Style::default(),
+        )
+        .unwrap();
+
+        assert!(filter.is_match("timeout", &record, "app.log"));
+        assert!(!filter.is_match("timeout", &record, "other.log"));
+        assert!(filter.is_match("time out", &record, "other.log"));
+    }
+
+    #[test]
+    fn errors_point_into_the_whole_pattern() {
+        assert_eq!(error("expr:(a"), "missing ) for ( at column 6");
+        assert_eq!(error("expr:a and"), "expected a term at column 11");
+        assert_eq!(error("expr:level:loud"), "unknown level loud at column 6");
+        assert_eq!(error("expr:a b"), "unexpected input at column 8");
+        assert_eq!(
+            error(r#"expr:text:"open"#),
+            "unterminated quote starting at column 11"
+        );
+    }
+}
@@ -0,0 +1,134 @@
+use crate::config::APP_NAME;
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+
+const HISTORY_FILE_NAME: &str = "search_history";
+const MAX_HISTORY_ENTRIES: usize = 500;
+
+/// Past search queries, oldest first, kept in `$XDG_DATA_HOME/log_watcher/search_history`
+/// or `~/.local/share/log_watcher/search_history`. History is a convenience, so failing to
+/// read or write the file only means it is not remembered between runs.
+pub struct SearchHistory {
+    pub entries: Vec<String>,
+    path: Option<PathBuf>,
+}
+
+impl SearchHistory {
+    pub fn load() -> SearchHistory {
+        SearchHistory::load_from(history_path())
+    }
+
+    /// Reads the history kept in `path`, or starts one only kept in memory without a path.
+    pub fn load_from(path: Option<PathBuf>) -> SearchHistory {
+        let entries = match &path {
+            Some(path) => fs::read_to_string(path)
+                .map(|content| {
+                    content
+                        .lines()
+                        .filter(|line| !line.is_empty())
+                        .map(str::to_string)
+                        .collect()
+                })
+                .unwrap_or_default(),
+            None => vec![],
+        };
+
+        SearchHistory { entries, path }
+    }
+
+    /// Moves the query to the end of the history, adding it if it is new.
+    pub fn add(&mut self, query: &str) {
+        if query.is_empty() || query.contains('\n') {
+            return;
+        }
+
+        self.entries.retain(|entry| entry != query);
+        self.entries.push(query.to_string());
+
+        if self.entries.len() > MAX_HISTORY_ENTRIES {
+            let excess_count = self.entries.len() - MAX_HISTORY_ENTRIES;
+            self.entries.drain(..excess_count);
+        }
+
+        self.save();
+    }
+
+    fn save(&self) {
+        let path = match &self.path {
+            Some(path) => path,
+            None => return,
+        };
+
+        if let Some(directory) = path.parent() {
+            fs::create_dir_all(directory).ok();
+        }
+
+        let mut content = self.entries.join("\n");
+        content.push('\n');
+
+        fs::write(path, content).ok();
+    }
+}
+
+fn history_path() -> Option<PathBuf> {
+    let data_home = match env::var_os("XDG_DATA_HOME") {
+        Some(data_home) => PathBuf::from(data_home),
+        None => PathBuf::from(env::var_os("HOME")?)
+            .join(".local")
+            .join("share"),
+    };
+
+    Some(data_home.join(APP_NAME).join(HISTORY_FILE_NAME))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn queries_move_to_the_end_and_are_kept_between_runs() {
+        let directory = tempfile::tempdir().unwrap();
+        let path = directory.path().join("nested").join(HISTORY_FILE_NAME);
+        let mut history = SearchHistory::load_from(Some(path.clone()));
+
+        history.add("error");
+        history.add("timeout");
+        history.add("error");
+        history.add("");
+        history.add("two\nlines");
+
+        assert_eq!(history.entries, vec!["timeout", "error"]);
+        assert_eq!(
+            SearchHistory::load_from(Some(path)).entries,
+            vec!["timeout", "error"]
+        );
+    }
+
+    #[test]
+    fn only_the_newest_queries_are_kept() {
+        let mut history = SearchHistory::load_from(None);
+
+        for query in 0..MAX_HISTORY_ENTRIES + 2 {
+            history.add(&query.to_string());
+        }
+
+        assert_eq!(history.entries.len(), MAX_HISTORY_ENTRIES);
+        assert_eq!(history.entries[0], "2");
+        assert_eq!(
+            history.entries.last().unwrap(),
+            &(MAX_HISTORY_ENTRIES + 1).to_string()
+        );
+    }
+
+    #[test]
+    fn an_unreadable_history_starts_empty() {
+        let directory = tempfile::tempdir().unwrap();
+
+        assert!(
+            SearchHistory::load_from(Some(directory.path().to_path_buf()))
+                .entries
+                .is_empty()
+        );
+    }
+}
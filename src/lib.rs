@@ -1,111 +1,206 @@
+pub mod classifier;
+pub mod config;
+pub mod filter;
+pub mod history;
+pub mod multiline;
+pub mod parser;
+pub mod reader;
+pub mod searcher;
 pub mod state;
+pub mod store;
+pub mod theme;
+pub mod widgets;
+
+pub use config::Config;
 
 extern crate termion;
 
-use failure::Error;
-use state::{InspectionState, SearchState, TabsState, WindowState};
-use std::io;
+use history::SearchHistory;
+use notify::{RawEvent, RecommendedWatcher, RecursiveMode, Watcher};
+use searcher::Searcher;
+use state::{
+    InspectionState, PromptState, SavedSearch, SearchState, SourcesState, TabsState, WindowState,
+};
+use std::fs;
+use std::path::{Path, PathBuf};
 use std::sync::mpsc;
 use std::thread;
 use std::time::Duration;
-use termion::event::Key;
+use termion::event::Event as TermEvent;
 use termion::input::TermRead;
-use toml::Value;
-
-const CONFIG_FILE_NAME: &str = "config.toml";
-const CONFIG_LOG_PATH_TOML_PROPERTY: &str = "log_path";
-const MESSAGE_FILTERS_TOML_PROPERTY: &str = "message_filters";
+use theme::Theme;
 
 pub struct App<'a> {
     pub tabs: TabsState,
+    pub sources: SourcesState,
     pub messages_window: WindowState<'a>,
-    pub search: SearchState<'a>,
+    pub search: SearchState,
     pub inspection_window: InspectionState<'a>,
+    pub prompt: PromptState,
+    pub theme: Theme,
 }
 
+const WATCHED_TICK_RATE_MS: u64 = 1000;
+const POLLING_TICK_RATE_MS: u64 = 100;
+
 pub enum Event<I> {
     Input(I),
+    LogChanged,
+    SearchProgress,
     Tick,
 }
 
 pub struct Events {
-    rx: mpsc::Receiver<Event<Key>>,
+    rx: mpsc::Receiver<Event<TermEvent>>,
+    tx: mpsc::Sender<Event<TermEvent>>,
+    _watcher: Option<RecommendedWatcher>,
 }
 
-pub struct Config {
-    pub log_path: String,
-    pub message_filters: Vec<String>,
+/// Wakes up the main loop when a log source has new lines or a search found more matches.
+#[derive(Clone)]
+pub struct Notifier {
+    tx: mpsc::Sender<Event<TermEvent>>,
 }
 
 impl<'a> App<'a> {
-    pub fn new(message_filters: &[String]) -> App<'a> {
+    pub fn new(
+        message_filters: &[String],
+        source_labels: &[String],
+        theme: &Theme,
+        searcher: Searcher,
+        search_history: SearchHistory,
+        saved_searches: Vec<SavedSearch>,
+    ) -> App<'a> {
         App {
             tabs: TabsState::new(message_filters),
-            messages_window: WindowState::new(),
-            search: SearchState::new(),
+            sources: SourcesState::new(source_labels),
+            messages_window: WindowState::new(theme.selection),
+            search: SearchState::new(searcher, search_history, saved_searches),
             inspection_window: InspectionState::new(),
+            prompt: PromptState::new(),
+            theme: theme.clone(),
         }
     }
 }
 
 impl Default for Events {
     fn default() -> Self {
-        Self::new()
+        Self::new(None, &[])
+    }
+}
+
+impl Notifier {
+    pub fn notify(&self) {
+        self.tx.send(Event::LogChanged).ok();
+    }
+
+    pub fn notify_search_progress(&self) {
+        self.tx.send(Event::SearchProgress).ok();
     }
 }
 
 impl Events {
-    pub fn new() -> Events {
+    /// Sends key and mouse input, a `LogChanged` event whenever one of the watched files changes and a
+    /// tick at `tick_rate`. The tick defaults to a slow clock refresh when file changes can be
+    /// watched and to fast polling when they cannot.
+    pub fn new(tick_rate: Option<Duration>, watched_paths: &[String]) -> Events {
         let (tx, rx) = mpsc::channel();
 
         let _input_handle = {
             let tx = tx.clone();
             thread::spawn(move || {
-                let stdin = io::stdin();
-                for evt in stdin.keys() {
-                    if let Ok(key) = evt {
-                        if tx.send(Event::Input(key)).is_err() {
-                            return;
-                        }
+                // Input is read from the terminal itself so stdin stays free to be a log source
+                let input_events = match termion::get_tty() {
+                    Ok(tty) => tty.events(),
+                    Err(_) => return,
+                };
+
+                for input_event in input_events.flatten() {
+                    if tx.send(Event::Input(input_event)).is_err() {
+                        return;
                     }
                 }
             })
         };
 
+        let watcher = watch_log_files(watched_paths, tx.clone()).ok();
+
+        let tick_rate = tick_rate.unwrap_or_else(|| match watcher {
+            Some(_) => Duration::from_millis(WATCHED_TICK_RATE_MS),
+            None => Duration::from_millis(POLLING_TICK_RATE_MS),
+        });
+
         let _tick_handle = {
+            let tx = tx.clone();
             thread::spawn(move || loop {
-                tx.send(Event::Tick).unwrap();
-                thread::sleep(Duration::from_millis(100));
+                if tx.send(Event::Tick).is_err() {
+                    return;
+                }
+
+                thread::sleep(tick_rate);
             })
         };
 
-        Events { rx }
+        Events {
+            rx,
+            tx,
+            _watcher: watcher,
+        }
+    }
+
+    pub fn notifier(&self) -> Notifier {
+        Notifier {
+            tx: self.tx.clone(),
+        }
     }
 
-    pub fn next(&self) -> Result<Event<Key>, mpsc::RecvError> {
+    pub fn next(&self) -> Result<Event<TermEvent>, mpsc::RecvError> {
         self.rx.recv()
     }
+
+    pub fn try_next(&self) -> Option<Event<TermEvent>> {
+        self.rx.try_recv().ok()
+    }
 }
 
-impl Config {
-    pub fn new() -> Result<Config, Error> {
-        let config = std::fs::read_to_string(CONFIG_FILE_NAME)
-            .expect("Failed loading config file")
-            .parse::<Value>()
-            .expect("Failed loading config values");
-
-        let log_path = config[CONFIG_LOG_PATH_TOML_PROPERTY]
-            .as_str()
-            .expect("Failed loading config value log_path");
-
-        let message_filters = config[MESSAGE_FILTERS_TOML_PROPERTY]
-            .clone()
-            .try_into::<Vec<String>>()
-            .expect("Failed loading config value captured_events");
-
-        Ok(Config {
-            log_path: log_path.to_string(),
-            message_filters,
-        })
+/// Watches the directories of the log files rather than the files themselves, so rotation
+/// that replaces a file is noticed as well.
+fn watch_log_files(
+    log_paths: &[String],
+    tx: mpsc::Sender<Event<TermEvent>>,
+) -> Result<RecommendedWatcher, failure::Error> {
+    let (watch_tx, watch_rx) = mpsc::channel();
+    let mut watcher = notify::raw_watcher(watch_tx)?;
+    let mut watched_files: Vec<PathBuf> = vec![];
+
+    for log_path in log_paths.iter().filter(|path| *path != "-") {
+        let path = Path::new(log_path);
+        let directory = match path.parent() {
+            Some(parent) if parent != Path::new("") => fs::canonicalize(parent)?,
+            _ => fs::canonicalize(".")?,
+        };
+
+        if let Some(file_name) = path.file_name() {
+            watched_files.push(directory.join(file_name));
+        }
+
+        watcher.watch(&directory, RecursiveMode::NonRecursive)?;
     }
+
+    thread::spawn(move || {
+        for event in watch_rx {
+            let RawEvent { path, .. } = event;
+
+            let is_watched = match path {
+                Some(path) => watched_files.contains(&path),
+                None => true,
+            };
+
+            if is_watched && tx.send(Event::LogChanged).is_err() {
+                return;
+            }
+        }
+    });
+
+    Ok(watcher)
 }
@@ -5,48 +5,168 @@ extern crate tui;
 
 use chrono::prelude::*;
 use failure::Error;
+use log_watcher::classifier::ALL_MESSAGES_INDEX;
+use log_watcher::config::default_filter_style;
+use log_watcher::filter::MessageFilter;
+use log_watcher::history::SearchHistory;
+use log_watcher::multiline::EntryGrouper;
+use log_watcher::parser::LogRecord;
+use log_watcher::reader::{self, LogEvent, LogSource, PipeReader, SeriesReader};
+use log_watcher::searcher::Searcher;
+use log_watcher::state::{search_pattern, JumpTarget, PromptPurpose};
+use log_watcher::store::{LineId, MessageStore, StoredLine};
+use log_watcher::widgets::{title_ranges, MessageList, TabBar};
 use log_watcher::{App, Config, Event, Events};
-use std::fs::File;
-use std::io::{self, stdout, BufReader, Stdout, Write};
+use std::borrow::Cow;
+use std::io::{self, stdout, Stdout, Write};
+use std::process;
+use std::sync::{Arc, RwLock};
 use termion::cursor::Goto;
-use termion::event::Key;
+use termion::event::{Event as TermEvent, Key, MouseButton, MouseEvent};
+use termion::input::MouseTerminal;
 use termion::raw::{IntoRawMode, RawTerminal};
 use termion::screen::AlternateScreen;
 use tui::backend::TermionBackend;
-use tui::layout::{Alignment, Constraint, Corner, Direction, Layout};
-use tui::style::{Color, Style};
-use tui::widgets::{Block, Borders, List, Paragraph, Tabs, Text, Widget};
+use tui::layout::{Alignment, Constraint, Direction, Layout, Rect};
+use tui::style::Style;
+use tui::widgets::{Block, Borders, Paragraph, Text, Widget};
 use tui::Terminal;
 
-const ALL_MESSAGES_INDEX: usize = 0;
+type AppTerminal = Terminal<TermionBackend<AlternateScreen<MouseTerminal<RawTerminal<Stdout>>>>>;
+
+const WHEEL_SCROLL_LINES: usize = 3;
+const BACK_LOAD_BYTES: u64 = 1024 * 1024;
+
+/// What scrolling past the oldest line found before where the files started being read.
+enum OlderLines {
+    Loaded,
+    AtStart,
+    Refused,
+    Failed(String),
+}
 
 fn main() -> Result<(), failure::Error> {
-    let config = Config::new()?;
-    let file = File::open(config.log_path).expect("Failed opening file");
-    let events = Events::new();
+    let mut config = Config::load().unwrap_or_else(|error| exit_with_error(error));
+    let events = Events::new(config.tick_rate, &config.log_paths);
+    let mut readers =
+        open_log_sources(&config, &events).unwrap_or_else(|error| exit_with_error(error));
 
-    let mut reader = BufReader::new(file);
-    let mut app = App::new(&config.message_filters);
-    let mut terminal = setup_terminal()?;
-    let mut captured_messages: Vec<Vec<Text>> = vec![];
+    let source_labels = reader::source_labels(&readers);
+    let mut groupers: Vec<EntryGrouper> = readers
+        .iter()
+        .map(|_| EntryGrouper::new(config.entry_rules.clone()))
+        .collect();
+    let filter_names: Vec<String> = config
+        .message_filters
+        .iter()
+        .map(|filter| filter.name.clone())
+        .collect();
+    let mut store = MessageStore::new(config.message_filters.len() + 1, config.retention)?;
 
-    for _ in 0..=config.message_filters.len() {
-        captured_messages.push(vec![]);
+    for (source, reader) in readers.iter_mut().enumerate() {
+        if let LogSource::Mapped(reader) = reader {
+            store.add_map(source, reader.map(), config.parser.clone());
+            reader.start_indexing(&source_labels[source], &config.classifier);
+        }
     }
 
+    let captured_messages = Arc::new(RwLock::new(store));
+    let searcher = Searcher::new(Arc::clone(&captured_messages), events.notifier());
+    let mut app = App::new(
+        &filter_names,
+        &source_labels,
+        &config.theme,
+        searcher,
+        SearchHistory::load(),
+        std::mem::take(&mut config.saved_searches),
+    );
+    let mut terminal = setup_terminal()?;
+
+    let mut drawn_second = None;
+
     loop {
-        read_user_input(&events, &mut app)?;
-        read_log(&mut reader, &config.message_filters, &mut captured_messages);
-        draw_ui(&mut terminal, &mut app, &captured_messages)?;
+        let has_updates = read_events(
+            &events,
+            &mut app,
+            &mut config,
+            &captured_messages,
+            &source_labels,
+        )?;
+        let mut has_new_lines = read_log(
+            &mut readers,
+            &mut groupers,
+            &source_labels,
+            &config,
+            &mut captured_messages.write().unwrap(),
+        )?;
+
+        // Scrolling past the oldest line reads further back into files opened at their tail
+        if std::mem::take(&mut app.messages_window.wants_older_lines) {
+            let older_lines = load_older_lines(
+                &mut readers,
+                &source_labels,
+                &config,
+                &mut captured_messages.write().unwrap(),
+            )?;
+
+            match &older_lines {
+                OlderLines::Loaded | OlderLines::Failed(_) => {
+                    // Older lines have lower ids than every match, so the search starts
+                    // over, and the filtered view with it as its results are gone until the
+                    // worker answers
+                    if app.search.is_filtering() {
+                        app.messages_window.reset();
+                    }
+
+                    app.search.invalidate();
+                }
+                OlderLines::AtStart => {
+                    app.tabs.status =
+                        Some("No older lines, the logs are shown from their start".to_string())
+                }
+                OlderLines::Refused => {
+                    app.tabs.status = Some(
+                        "No older lines are loaded once the retention limits dropped a line"
+                            .to_string(),
+                    )
+                }
+            }
+
+            if let OlderLines::Failed(error) = older_lines {
+                app.tabs.status = Some(error);
+            }
 
-        if app.search.is_initiated && !app.inspection_window.is_initiated {
+            has_new_lines = true;
+        }
+        let current_second = Utc::now().timestamp();
+
+        // The clock in the title is the only thing that changes without updates or new lines
+        if !has_updates && !has_new_lines && drawn_second == Some(current_second) {
+            continue;
+        }
+
+        draw_ui(
+            &mut terminal,
+            &mut app,
+            &config,
+            &captured_messages.read().unwrap(),
+        )?;
+        drawn_second = Some(current_second);
+
+        let cursor_location = if app.inspection_window.is_initiated {
+            None
+        } else if app.prompt.is_initiated {
+            Some(app.prompt.get_cursor_location())
+        } else if app.search.is_editing {
+            Some(app.search.get_cursor_location())
+        } else {
+            None
+        };
+
+        if let Some(cursor_location) = cursor_location {
             terminal.show_cursor()?;
 
-            write!(
-                terminal.backend_mut(),
-                "{}",
-                Goto(2 + &app.search.get_cursor_location(), 2)
-            )?;
+            write!(terminal.backend_mut(), "{}", Goto(2 + cursor_location, 2))?;
 
             io::stdout().flush().ok();
         } else {
@@ -55,9 +175,53 @@ fn main() -> Result<(), failure::Error> {
     }
 }
 
-fn setup_terminal() -> Result<Terminal<TermionBackend<AlternateScreen<RawTerminal<Stdout>>>>, Error>
-{
+fn open_log_sources(config: &Config, events: &Events) -> Result<Vec<LogSource>, Error> {
+    let mut sources = vec![];
+
+    for log_path in &config.log_paths {
+        if log_path == "-" {
+            sources.push(LogSource::Pipe(PipeReader::stdin(events.notifier())));
+        } else if config.is_static {
+            sources.push(LogSource::open_static(
+                log_path,
+                config.entry_rules.clone(),
+                events.notifier(),
+            )?);
+        } else if config.rotated {
+            sources.push(LogSource::Series(SeriesReader::open(
+                log_path,
+                config.follow,
+                events.notifier(),
+            )?));
+        } else {
+            sources.push(LogSource::open(
+                log_path,
+                config.tail_lines,
+                config.tail_bytes,
+                config.follow,
+                events.notifier(),
+            )?);
+        }
+    }
+
+    for command in &config.commands {
+        sources.push(LogSource::Pipe(PipeReader::command(
+            command,
+            events.notifier(),
+        )?));
+    }
+
+    Ok(sources)
+}
+
+fn exit_with_error(error: Error) -> ! {
+    eprintln!("log_watcher: {}", error);
+    process::exit(1);
+}
+
+fn setup_terminal() -> Result<AppTerminal, Error> {
     let stdout = stdout().into_raw_mode()?;
+    let stdout = MouseTerminal::from(stdout);
     let stdout = AlternateScreen::from(stdout);
     let backend = TermionBackend::new(stdout);
     let terminal = Terminal::new(backend)?;
@@ -66,12 +230,25 @@ fn setup_terminal() -> Result<Terminal<TermionBackend<AlternateScreen<RawTermina
 }
 
 fn draw_ui<'a>(
-    terminal: &mut Terminal<TermionBackend<AlternateScreen<RawTerminal<Stdout>>>>,
+    terminal: &mut AppTerminal,
     app: &mut App<'a>,
-    captured_messages: &[Vec<Text<'a>>],
+    config: &Config,
+    captured_messages: &MessageStore,
 ) -> Result<(), std::io::Error> {
     terminal.draw(|mut f| {
-        let current_time_string = Utc::now().format("%Y-%m-%d-%H:%M:%S").to_string();        
+        let current_time_string = Utc::now().format("%Y-%m-%d-%H:%M:%S").to_string();
+        let mut tabs_title = if app.sources.has_multiple() {
+            format!(
+                "{} | {}",
+                current_time_string, app.sources.titles[app.sources.index]
+            )
+        } else {
+            current_time_string.clone()
+        };
+
+        if let Some(status) = &app.tabs.status {
+            tabs_title = format!("{} | {}", tabs_title, status);
+        }
 
         let constraints = if app.inspection_window.is_initiated {
             [Constraint::Percentage(100)].as_ref()
@@ -84,13 +261,23 @@ fn draw_ui<'a>(
             .constraints(constraints)
             .split(f.size());
 
+        let captured_counts: Vec<usize> = (0..app.tabs.titles.len())
+            .map(|tab| captured_messages.captured_count(tab))
+            .collect();
+        app.tabs
+            .update_activity(&captured_counts, Utc::now().timestamp());
+
+        let theme = &app.theme;
+
         Block::default()
-            .style(Style::default().bg(Color::White))
+            .style(theme.background)
             .render(&mut f, chunks[0]);
 
         if app.inspection_window.is_initiated {
-            app.inspection_window
-                .inspect(app.messages_window.selected_line.as_ref().unwrap());
+            app.inspection_window.inspect(
+                app.messages_window.selected_line.as_ref().unwrap(),
+                &config.parser,
+            );
 
             Paragraph::new(
                 [app.inspection_window.text.as_ref().unwrap()]
@@ -100,6 +287,7 @@ fn draw_ui<'a>(
             .block(
                 Block::default()
                     .borders(Borders::ALL)
+                    .border_style(theme.border)
                     .title(&current_time_string),
             )
             .alignment(Alignment::Left)
@@ -110,144 +298,792 @@ fn draw_ui<'a>(
             return;
         }
 
-        if app.search.is_initiated {
-            Paragraph::new([Text::raw(&app.search.input)].iter())
+        app.tabs.title_row = None;
+
+        let tab_ids = captured_messages.tab_ids(app.tabs.index);
+        let tab_messages: Cow<[LineId]> = if app.sources.index == 0 {
+            Cow::Borrowed(tab_ids)
+        } else {
+            Cow::Owned(
+                tab_ids
+                    .iter()
+                    .filter(|id| match captured_messages.source(**id) {
+                        Some(source) => app.sources.is_selected(source),
+                        None => false,
+                    })
+                    .cloned()
+                    .collect(),
+            )
+        };
+
+        let has_search_input = app.search.is_initiated && !app.search.input.is_empty();
+
+        if has_search_input {
+            app.search.update_results(&tab_messages);
+        }
+
+        let all_messages: &[LineId] = if app.search.is_filtering() {
+            &app.search.results
+        } else {
+            &tab_messages
+        };
+        let shown_messages = app.messages_window.anchor_lines(all_messages);
+
+        if let Some(is_older) = app.search.pending_jump.take() {
+            app.messages_window
+                .jump_to_match(shown_messages, &app.search.results, is_older);
+        }
+
+        if let Some(target) = app.messages_window.pending_target.take() {
+            if let Err(error) = app.messages_window.jump_to(
+                &target,
+                shown_messages,
+                captured_messages,
+                &config.parser,
+            ) {
+                app.tabs.status = Some(error);
+            }
+        }
+
+        // Jumping selects a line, which keeps the view on the lines there are now. Anchoring
+        // the uncut lines again keeps counting the new lines after an earlier anchor
+        let shown_messages = app.messages_window.anchor_lines(all_messages);
+
+        app.messages_window.display_lines(
+            shown_messages,
+            captured_messages,
+            chunks[1],
+            &app.sources,
+            config,
+            if has_search_input {
+                app.search.matcher.as_ref()
+            } else {
+                None
+            },
+        );
+
+        let messages_title = match app
+            .search
+            .match_counter(app.messages_window.selected_id(shown_messages))
+        {
+            Some(match_counter) => format!("Messages | {}", match_counter),
+            None => "Messages".to_string(),
+        };
+
+        if app.prompt.is_initiated {
+            Paragraph::new([Text::raw(&app.prompt.input)].iter())
                 .block(
                     Block::default()
                         .borders(Borders::ALL)
-                        .title(&current_time_string),
+                        .border_style(theme.border)
+                        .title(&format!("{} | {}", current_time_string, app.prompt.title)),
                 )
+                .style(theme.search)
                 .alignment(Alignment::Left)
-                .wrap(true)
                 .render(&mut f, chunks[0]);
-        } else {
-            Tabs::default()
+        } else if app.search.is_initiated {
+            let mut search_text = vec![Text::raw(&app.search.input)];
+
+            if let (Some(error), false) = (&app.search.error, app.search.input.is_empty()) {
+                search_text.push(Text::styled(format!("  {}", error), theme.search_error));
+            }
+
+            if let Some(progress) = app.search.progress() {
+                search_text.push(Text::styled(
+                    format!("  searching {}%", progress),
+                    theme.search_progress,
+                ));
+            }
+
+            Paragraph::new(search_text.iter())
                 .block(
                     Block::default()
                         .borders(Borders::ALL)
-                        .title(&current_time_string),
+                        .border_style(theme.border)
+                        .title(&format!(
+                            "{} | {}",
+                            current_time_string,
+                            app.search.mode_title()
+                        )),
                 )
-                .titles(&app.tabs.titles)
-                .select(app.tabs.index)
-                .style(Style::default().fg(Color::Cyan))
-                .highlight_style(Style::default().fg(Color::Yellow))
+                .style(theme.search)
+                .alignment(Alignment::Left)
+                .wrap(true)
+                .render(&mut f, chunks[0]);
+        } else {
+            let tab_styles: Vec<Style> = (0..app.tabs.titles.len())
+                .map(|tab| {
+                    if tab == app.tabs.index {
+                        theme.tab_highlight
+                    } else if app.tabs.is_flashing(tab) {
+                        theme.tab_activity
+                    } else if app.tabs.has_unread(tab) {
+                        theme.tab_unread
+                    } else {
+                        theme.tab_bar
+                    }
+                })
+                .collect();
+
+            let tab_titles = app.tabs.display_titles();
+            let tabs_block = Block::default()
+                .borders(Borders::ALL)
+                .border_style(theme.border)
+                .title(&tabs_title);
+            let tabs_area = tabs_block.inner(chunks[0]);
+
+            // Remembered so clicks can be matched with the tabs
+            app.tabs.title_ranges = title_ranges(&tab_titles, tabs_area);
+            app.tabs.title_row = Some(tabs_area.top());
+
+            TabBar::new(&tab_titles, &tab_styles)
+                .block(tabs_block)
+                .style(theme.tab_bar)
                 .render(&mut f, chunks[0]);
         }
 
-        if app.search.is_initiated && !app.search.input.is_empty() {
-            app.messages_window.display_lines(
-                &app.search.get_results(&captured_messages[app.tabs.index]),
-                chunks[1].height as usize,
-            );
-        } else {
-            app.messages_window.display_lines(
-                &captured_messages[app.tabs.index],
-                chunks[1].height as usize,
+        MessageList::new(&app.messages_window.lines, &app.messages_window.highlights)
+            .block(
+                Block::default()
+                    .borders(Borders::ALL)
+                    .border_style(theme.border)
+                    .title(&messages_title),
+            )
+            .highlight_style(theme.highlight)
+            .wrap(app.messages_window.is_wrapping)
+            .horizontal_offset(app.messages_window.horizontal_offset)
+            .render(&mut f, chunks[1]);
+
+        if app.messages_window.is_paused || app.messages_window.new_line_count > 0 {
+            let footer = if app.messages_window.new_line_count > 0 {
+                format!(
+                    " ▼ {} new lines | p to follow ",
+                    app.messages_window.new_line_count
+                )
+            } else {
+                " paused | p to follow ".to_string()
+            };
+            let footer_area = Rect::new(
+                chunks[1].x + 1,
+                chunks[1].bottom() - 1,
+                chunks[1].width.saturating_sub(2),
+                1,
             );
-        };
 
-        List::new(app.messages_window.lines.iter().cloned())
-            .block(Block::default().borders(Borders::ALL).title("Messages"))
-            .start_corner(Corner::BottomLeft)
-            .render(&mut f, chunks[1]);
+            Paragraph::new([Text::styled(footer, theme.marker)].iter())
+                .alignment(Alignment::Right)
+                .render(&mut f, footer_area);
+        }
     })
 }
 
-fn read_user_input(events: &Events, app: &mut App) -> Result<(), Error> {
+/// Waits for the next event, then drains whatever else is already queued so a burst of
+/// file changes or key presses results in a single read and redraw. Tells whether key
+/// input or search progress changed what is shown.
+fn read_events(
+    events: &Events,
+    app: &mut App,
+    config: &mut Config,
+    captured_messages: &RwLock<MessageStore>,
+    source_labels: &[String],
+) -> Result<bool, Error> {
+    let mut event = Some(events.next()?);
+    let mut has_updates = false;
+
+    while let Some(current) = event {
+        match current {
+            Event::Input(TermEvent::Key(input)) => {
+                read_user_input(input, app, config, captured_messages, source_labels)?;
+                has_updates = true;
+            }
+            Event::Input(TermEvent::Mouse(input)) => {
+                read_mouse_input(input, app);
+                has_updates = true;
+            }
+            Event::Input(TermEvent::Unsupported(_)) => {}
+            Event::SearchProgress => has_updates = true,
+            Event::LogChanged | Event::Tick => {}
+        }
+
+        event = events.try_next();
+    }
+
+    Ok(has_updates)
+}
+
+fn read_user_input(
+    input: Key,
+    app: &mut App,
+    config: &mut Config,
+    captured_messages: &RwLock<MessageStore>,
+    source_labels: &[String],
+) -> Result<(), Error> {
     //TODO: Group and cleanup
-    if let Event::Input(input) = events.next()? {
-        match input {
-            Key::Char(c)
-                if app.search.is_initiated && !app.inspection_window.is_initiated && c != '\n' =>
-            {
-                app.search.add_input(c);
-                app.messages_window.reset();
-            }
-            Key::Backspace if app.search.is_initiated && !app.inspection_window.is_initiated => {
-                app.search.remove_input_backspace();
-                app.messages_window.reset();
-            }
-            Key::Delete if app.search.is_initiated && !app.inspection_window.is_initiated => {
-                app.search.remove_input_delete();
-                app.messages_window.reset();
-            }
-            Key::Left if app.search.is_initiated && !app.inspection_window.is_initiated => {
-                app.search.cursor_move_left()
-            }
-            Key::Right if app.search.is_initiated && !app.inspection_window.is_initiated => {
-                app.search.cursor_move_right()
-            }
-            Key::Esc if app.inspection_window.is_initiated => app.inspection_window.close(),
-            Key::Esc if app.search.is_initiated => {
-                app.search.close();
-                app.messages_window.reset()
-            }
-            Key::Esc if app.messages_window.line_is_selected => app.messages_window.reset(),
-            Key::Char('q') => failure::bail!("User called Quit"),
-            Key::Right => switch_tab(app, true),
-            Key::Left => switch_tab(app, false),
-            Key::Up if app.inspection_window.is_initiated => app.inspection_window.scroll_up(),
-            Key::Up => app.messages_window.previous(),
-            Key::Down if app.inspection_window.is_initiated => app.inspection_window.scroll_down(),
-            Key::Down => app.messages_window.next(),
-            Key::Char('f') => {
-                app.messages_window.reset();
-                app.search.initiate();
-            }
-            Key::Char('\n') => app.inspection_window.initiate(),
-            _ => {}
+    match input {
+        Key::Char('\n') if app.prompt.is_initiated => {
+            let input = app.prompt.input.trim().to_string();
+
+            match app.prompt.purpose {
+                PromptPurpose::RenameTab if !input.is_empty() => rename_tab(app, config, &input),
+                PromptPurpose::RenameTab => {}
+                PromptPurpose::JumpTo => match JumpTarget::parse(&input) {
+                    Some(target) => app.messages_window.pending_target = Some(target),
+                    None => {
+                        app.tabs.status = Some(format!("Not a line number or timestamp: {}", input))
+                    }
+                },
+            }
+
+            app.prompt.close();
         }
-    };
+        Key::Char(c) if app.prompt.is_initiated => app.prompt.add_input(c),
+        Key::Backspace if app.prompt.is_initiated => app.prompt.remove_input_backspace(),
+        Key::Left if app.prompt.is_initiated => app.prompt.cursor_move_left(),
+        Key::Right if app.prompt.is_initiated => app.prompt.cursor_move_right(),
+        Key::Esc if app.prompt.is_initiated => app.prompt.close(),
+        _ if app.prompt.is_initiated => {}
+        Key::Char(c)
+            if app.search.is_editing && !app.inspection_window.is_initiated && c != '\n' =>
+        {
+            app.search.add_input(c);
+            app.messages_window.reset();
+        }
+        Key::Backspace if app.search.is_editing && !app.inspection_window.is_initiated => {
+            app.search.remove_input_backspace();
+            app.messages_window.reset();
+        }
+        Key::Delete if app.search.is_editing && !app.inspection_window.is_initiated => {
+            app.search.remove_input_delete();
+            app.messages_window.reset();
+        }
+        Key::Char('\n') if app.search.is_editing && !app.inspection_window.is_initiated => {
+            app.search.finish_editing()
+        }
+        Key::Ctrl('f') if app.search.is_initiated && !app.inspection_window.is_initiated => {
+            app.search.toggle_highlighting();
+            app.messages_window.reset();
+        }
+        Key::Ctrl('r') if app.search.is_editing && !app.inspection_window.is_initiated => {
+            app.search.toggle_regex();
+            app.messages_window.reset();
+        }
+        Key::Ctrl('s') if app.search.is_editing && !app.inspection_window.is_initiated => {
+            app.search.toggle_case_sensitive();
+            app.messages_window.reset();
+        }
+        Key::Ctrl('w') if app.search.is_editing && !app.inspection_window.is_initiated => {
+            app.search.toggle_whole_word();
+            app.messages_window.reset();
+        }
+        Key::Left if app.search.is_editing && !app.inspection_window.is_initiated => {
+            app.search.cursor_move_left()
+        }
+        Key::Right if app.search.is_editing && !app.inspection_window.is_initiated => {
+            app.search.cursor_move_right()
+        }
+        Key::Esc if app.inspection_window.is_initiated => app.inspection_window.close(),
+        Key::Esc if app.search.is_initiated => {
+            app.search.close();
+            app.messages_window.reset()
+        }
+        Key::Esc if app.messages_window.line_is_selected => app.messages_window.reset(),
+        Key::Alt(c) if !app.inspection_window.is_initiated && app.search.recall(c) => {
+            app.messages_window.reset()
+        }
+        Key::Char('q') => failure::bail!("User called Quit"),
+        Key::Char('s') => {
+            app.messages_window.reset();
+            app.search.invalidate();
+            app.sources.next();
+        }
+        Key::Right => switch_tab(app, true),
+        Key::Left => switch_tab(app, false),
+        Key::Up if app.inspection_window.is_initiated => app.inspection_window.scroll_up(),
+        Key::Up if app.search.is_editing => {
+            app.search.history_previous();
+            app.messages_window.reset();
+        }
+        Key::Up => app.messages_window.previous(),
+        Key::Down if app.inspection_window.is_initiated => app.inspection_window.scroll_down(),
+        Key::Down if app.search.is_editing => {
+            app.search.history_next();
+            app.messages_window.reset();
+        }
+        Key::Down => app.messages_window.next(),
+        Key::Char('n') if app.search.is_initiated => app.search.jump(true),
+        Key::Char('N') if app.search.is_initiated => app.search.jump(false),
+        Key::Char('p') => app.messages_window.toggle_pause(),
+        Key::Char('w') => app.messages_window.toggle_wrap(),
+        Key::Char('z') => app.messages_window.toggle_entry(),
+        Key::Char('Z') => app.messages_window.toggle_all_entries(),
+        Key::Char('h') => app.messages_window.scroll_left(),
+        Key::Char('l') => app.messages_window.scroll_right(),
+        Key::PageUp if !app.inspection_window.is_initiated => app.messages_window.page_up(),
+        Key::PageDown if !app.inspection_window.is_initiated => app.messages_window.page_down(),
+        Key::Home | Key::Char('g') if !app.inspection_window.is_initiated => {
+            app.messages_window.first()
+        }
+        Key::End | Key::Char('G') if !app.inspection_window.is_initiated => {
+            app.messages_window.follow()
+        }
+        Key::Char(':') if !app.inspection_window.is_initiated => app.prompt.initiate(
+            PromptPurpose::JumpTo,
+            "Go to line number or timestamp (Enter to jump, Esc to cancel)",
+            "",
+        ),
+        Key::Char('f') => {
+            app.messages_window.reset();
+            app.search.initiate();
+        }
+        Key::Char('\n') if app.messages_window.line_is_selected => app.inspection_window.initiate(),
+        Key::Char('t')
+            if app.search.is_initiated
+                && !app.search.input.is_empty()
+                && app.search.error.is_none() =>
+        {
+            let name = app.search.input.clone();
+            let pattern = format!(
+                "re:{}",
+                search_pattern(
+                    &app.search.input,
+                    app.search.is_regex,
+                    app.search.is_case_sensitive,
+                    app.search.is_whole_word,
+                )
+            );
+
+            app.search.close();
+            add_tab(
+                app,
+                config,
+                captured_messages,
+                source_labels,
+                &name,
+                &pattern,
+            );
+        }
+        Key::Char('L') if app.messages_window.line_is_selected => {
+            if let Some(level) = selected_record(app, config).and_then(|record| record.level) {
+                let pattern = format!("expr:level:{}", level);
+
+                add_tab(
+                    app,
+                    config,
+                    captured_messages,
+                    source_labels,
+                    level.name(),
+                    &pattern,
+                );
+            } else {
+                app.tabs.status = Some("Selected line has no level".to_string());
+            }
+        }
+        Key::Char('O') if app.messages_window.line_is_selected => {
+            if let Some(logger) = selected_record(app, config).and_then(|record| record.logger) {
+                let pattern = format!(
+                    "expr:logger:\"{}\"",
+                    logger.replace('\\', "\\\\").replace('"', "\\\"")
+                );
+
+                add_tab(
+                    app,
+                    config,
+                    captured_messages,
+                    source_labels,
+                    &logger,
+                    &pattern,
+                );
+            } else {
+                app.tabs.status = Some("Selected line has no logger".to_string());
+            }
+        }
+        Key::Char('x') if app.tabs.index != ALL_MESSAGES_INDEX => {
+            close_tab(app, config, captured_messages)?
+        }
+        Key::Char('r') if app.tabs.index != ALL_MESSAGES_INDEX => {
+            let title = app.tabs.titles[app.tabs.index].clone();
+
+            app.prompt.initiate(
+                PromptPurpose::RenameTab,
+                "Rename tab (Enter to apply, Esc to cancel)",
+                &title,
+            );
+        }
+        Key::Char('<') => move_tab(app, config, captured_messages, false),
+        Key::Char('>') => move_tab(app, config, captured_messages, true),
+        Key::Char('W') if app.tabs.index != ALL_MESSAGES_INDEX => save_tab(app, config),
+        _ => {}
+    }
 
     Ok(())
 }
 
+fn read_mouse_input(input: MouseEvent, app: &mut App) {
+    let (button, x, y) = match input {
+        // Terminals count from 1 while the UI is laid out from 0
+        MouseEvent::Press(button, x, y) => (button, x.saturating_sub(1), y.saturating_sub(1)),
+        MouseEvent::Release(_, _) | MouseEvent::Hold(_, _) => return,
+    };
+
+    match button {
+        MouseButton::WheelUp if app.inspection_window.is_initiated => {
+            app.inspection_window.scroll_up()
+        }
+        MouseButton::WheelDown if app.inspection_window.is_initiated => {
+            app.inspection_window.scroll_down()
+        }
+        MouseButton::WheelUp => {
+            for _ in 0..WHEEL_SCROLL_LINES {
+                app.messages_window.previous();
+            }
+        }
+        MouseButton::WheelDown => {
+            for _ in 0..WHEEL_SCROLL_LINES {
+                app.messages_window.next();
+            }
+        }
+        MouseButton::Left if app.inspection_window.is_initiated || app.prompt.is_initiated => {}
+        MouseButton::Left => {
+            if let Some(index) = app.tabs.title_at(x, y) {
+                select_tab(app, index);
+            } else if app.messages_window.click(x, y) {
+                app.inspection_window.initiate();
+            }
+        }
+        MouseButton::Right | MouseButton::Middle => {}
+    }
+}
+
+/// Captures the entries completed by new lines, which are grouped per source, and the
+/// entries that stopped getting lines. Markers end the entry before them.
 fn read_log(
-    reader: &mut BufReader<File>,
-    message_types: &[String],
-    captured_messages: &mut [Vec<Text>],
-) {
-    use termion::input::TermRead;
+    readers: &mut [LogSource],
+    groupers: &mut [EntryGrouper],
+    source_labels: &[String],
+    config: &Config,
+    captured_messages: &mut MessageStore,
+) -> Result<bool, Error> {
+    let mut has_new_lines = false;
+
+    for (source, (reader, grouper)) in readers.iter_mut().zip(groupers.iter_mut()).enumerate() {
+        let source_label = &source_labels[source];
+
+        for event in reader.read_events() {
+            has_new_lines = true;
+
+            let now = Local::now().format("%Y-%m-%d %H:%M:%S");
+            let (entry, marker) = match event {
+                LogEvent::Line(message) => (grouper.push(message), None),
+                LogEvent::Rotated => (
+                    grouper.flush(),
+                    Some(format!("--- {} rotated at {} ---", reader.name(), now)),
+                ),
+                LogEvent::Truncated => (
+                    grouper.flush(),
+                    Some(format!("--- {} truncated at {} ---", reader.name(), now)),
+                ),
+                LogEvent::Ended => (
+                    grouper.flush(),
+                    Some(format!("--- {} ended at {} ---", reader.name(), now)),
+                ),
+                LogEvent::Failed(error) => (
+                    grouper.flush(),
+                    Some(format!(
+                        "--- {} failed at {}: {} ---",
+                        reader.name(),
+                        now,
+                        error
+                    )),
+                ),
+                LogEvent::Continued { from, to } => (
+                    grouper.flush(),
+                    Some(format!("--- end of {}, continued in {} ---", from, to)),
+                ),
+                LogEvent::Mapped(entry) => {
+                    captured_messages.push_mapped(source, entry.end, entry.style, &entry.tabs)?;
+
+                    (None, None)
+                }
+            };
 
-    while let Some(message) = reader.read_line().expect("Failed reading line") {
-        if message.is_empty() {
-            break;
+            if let Some(entry) = entry {
+                capture_message(source, source_label, config, captured_messages, &entry)?;
+            }
+
+            if let Some(marker) = marker {
+                capture_marker(source, config, captured_messages, marker)?;
+            }
         }
 
-        capture_message(message_types, captured_messages, &message);
+        if let Some(entry) = grouper.flush_expired() {
+            capture_message(source, source_label, config, captured_messages, &entry)?;
+            has_new_lines = true;
+        }
     }
+
+    Ok(has_new_lines)
 }
 
-fn capture_message(message_types: &[String], captured_messages: &mut [Vec<Text>], message: &str) {
-    let mut message_captured = false;
+fn capture_message(
+    source: usize,
+    source_label: &str,
+    config: &Config,
+    captured_messages: &mut MessageStore,
+    message: &str,
+) -> Result<(), Error> {
+    let (style, timestamp, tabs) = classify_message(source_label, config, message);
 
-    for (index, message_type) in message_types.iter().enumerate() {
-        if message.contains(message_type) {
-            let styled = Text::styled(
-                message.to_string(),
-                Style::default().fg(Color::Indexed((index + 1) as u8)),
-            );
+    captured_messages.push(
+        StoredLine {
+            source,
+            text: message.to_string(),
+            style,
+            timestamp,
+        },
+        &tabs,
+    )?;
 
-            captured_messages[index + 1].push(styled.clone());
-            captured_messages[ALL_MESSAGES_INDEX].push(styled);
+    Ok(())
+}
+
+/// Classifies a message with the filters of the current tabs, see `Classifier::classify`.
+fn classify_message(
+    source_label: &str,
+    config: &Config,
+    message: &str,
+) -> (Style, Option<NaiveDateTime>, Vec<usize>) {
+    config
+        .classifier
+        .read()
+        .unwrap()
+        .classify(source_label, message)
+}
+
+/// Reads a chunk from before where each file started being read and adds its entries in
+/// front of the captured ones. A file that cannot be read back is left out and reported,
+/// the chunks of the others are still added.
+fn load_older_lines(
+    readers: &mut [LogSource],
+    source_labels: &[String],
+    config: &Config,
+    captured_messages: &mut MessageStore,
+) -> Result<OlderLines, Error> {
+    if !captured_messages.accepts_older_lines() {
+        return Ok(OlderLines::Refused);
+    }
+
+    let mut older_lines = vec![];
+    let mut failure = None;
+
+    // The chunks go in front together, so each one does not land before the previous
+    // source's and they keep the order of the sources
+    for (source, reader) in readers.iter_mut().enumerate() {
+        let mut grouper = EntryGrouper::new(config.entry_rules.clone());
+        let mut entries = vec![];
 
-            message_captured = true;
+        let lines = match reader.read_older_lines(BACK_LOAD_BYTES) {
+            Ok(lines) => lines,
+            Err(error) => {
+                failure = Some(format!(
+                    "Failed loading older lines of {}: {}",
+                    reader.name(),
+                    error
+                ));
+
+                continue;
+            }
+        };
+
+        for line in lines {
+            entries.extend(grouper.push(line));
         }
+
+        entries.extend(grouper.flush());
+
+        older_lines.extend(entries.into_iter().map(|entry| {
+            let (style, timestamp, tabs) = classify_message(&source_labels[source], config, &entry);
+
+            (
+                StoredLine {
+                    source,
+                    text: entry,
+                    style,
+                    timestamp,
+                },
+                tabs,
+            )
+        }));
     }
 
-    if !message_captured {
-        let styled = Text::styled(message.to_string(), Style::default().fg(Color::White));
+    let is_loaded = !older_lines.is_empty();
 
-        captured_messages[ALL_MESSAGES_INDEX].push(styled);
+    if is_loaded {
+        captured_messages.prepend(older_lines)?;
     }
+
+    Ok(match failure {
+        Some(error) => OlderLines::Failed(error),
+        None if is_loaded => OlderLines::Loaded,
+        None => OlderLines::AtStart,
+    })
 }
 
-fn switch_tab(app: &mut App, is_next: bool) {
-    app.messages_window.reset();
+fn capture_marker(
+    source: usize,
+    config: &Config,
+    captured_messages: &mut MessageStore,
+    text: String,
+) -> Result<(), Error> {
+    let tabs: Vec<usize> = (0..=config.message_filters.len()).collect();
+
+    captured_messages.push(
+        StoredLine {
+            source,
+            text,
+            style: config.theme.marker,
+            timestamp: None,
+        },
+        &tabs,
+    )?;
 
+    Ok(())
+}
+
+fn switch_tab(app: &mut App, is_next: bool) {
     if is_next {
         app.tabs.next();
     } else {
         app.tabs.previous();
     }
+
+    select_tab(app, app.tabs.index);
+}
+
+/// Shows the tab at `index` from its newest line, searching it again if a search is open.
+fn select_tab(app: &mut App, index: usize) {
+    app.messages_window.reset();
+    app.search.invalidate();
+    app.tabs.status = None;
+    app.tabs.select(index);
+}
+
+/// Parses the selected line again for the parts of its record that are not stored with it.
+fn selected_record(app: &App, config: &Config) -> Option<LogRecord> {
+    match &app.messages_window.selected_line {
+        Some(Text::Raw(text)) | Some(Text::Styled(text, _)) => Some(config.parser.parse(text)),
+        None => None,
+    }
+}
+
+/// Adds a tab for the filter and selects it, filling it with the matching lines that are
+/// still retained. Problems are shown in the tab bar rather than ending the program.
+fn add_tab(
+    app: &mut App,
+    config: &mut Config,
+    captured_messages: &RwLock<MessageStore>,
+    source_labels: &[String],
+    name: &str,
+    pattern: &str,
+) {
+    let style = default_filter_style(config.message_filters.len());
+    let message_filter = match MessageFilter::new(name, pattern, style) {
+        Ok(message_filter) => message_filter,
+        Err(error) => {
+            app.tabs.status = Some(format!("Invalid filter: {}", error));
+
+            return;
+        }
+    };
+
+    let result = captured_messages.write().unwrap().add_tab(|line| {
+        let record = config.parser.parse(&line.text);
+
+        message_filter.is_match(&line.text, &record, &source_labels[line.source])
+    });
+
+    if let Err(error) = result {
+        app.tabs.status = Some(format!("Failed adding tab: {}", error));
+
+        return;
+    }
+
+    config.message_filters.push(message_filter);
+    config.update_classifier();
+    app.tabs.add(name);
+    app.tabs.status = Some(format!("Added tab {}, W to save it", name));
+    app.messages_window.reset();
+    app.search.invalidate();
+}
+
+fn close_tab(
+    app: &mut App,
+    config: &mut Config,
+    captured_messages: &RwLock<MessageStore>,
+) -> Result<(), Error> {
+    let index = app.tabs.index;
+
+    captured_messages.write().unwrap().remove_tab(index)?;
+    config.message_filters.remove(index - 1);
+    config.update_classifier();
+    app.tabs.remove(index);
+    app.tabs.status = None;
+    app.messages_window.reset();
+    app.search.invalidate();
+
+    Ok(())
+}
+
+fn rename_tab(app: &mut App, config: &mut Config, name: &str) {
+    let index = app.tabs.index;
+
+    app.tabs.titles[index] = name.to_string();
+    config.message_filters[index - 1].name = name.to_string();
+}
+
+fn move_tab(
+    app: &mut App,
+    config: &mut Config,
+    captured_messages: &RwLock<MessageStore>,
+    is_forward: bool,
+) {
+    if let Some((first, second)) = app.tabs.move_current(is_forward) {
+        captured_messages.write().unwrap().swap_tabs(first, second);
+        config.message_filters.swap(first - 1, second - 1);
+        config.update_classifier();
+    }
+}
+
+/// Writes a tab created at runtime to config.toml so it is there on the next start.
+fn save_tab(app: &mut App, config: &mut Config) {
+    let index = app.tabs.index;
+
+    // Appending a renamed tab would leave its old entry in the file as a second tab
+    match &app.tabs.saved_titles[index] {
+        Some(_) if !app.tabs.is_unsaved(index) => {
+            app.tabs.status = Some("Tab is already in the config file".to_string());
+
+            return;
+        }
+        Some(saved_title) => {
+            app.tabs.status = Some(format!(
+                "Tab is in the config file as {}, rename it there by hand",
+                saved_title
+            ));
+
+            return;
+        }
+        None => {}
+    }
+
+    let message_filter = &config.message_filters[index - 1];
+    let (name, pattern) = (message_filter.name.clone(), message_filter.pattern.clone());
+
+    app.tabs.status = Some(match config.append_filter(&name, &pattern) {
+        Ok(path) => {
+            app.tabs.saved_titles[index] = Some(name);
+
+            format!("Saved to {}", path.display())
+        }
+        Err(error) => error.to_string(),
+    });
 }
@@ -0,0 +1,223 @@
+use failure::Error;
+use regex::Regex;
+use std::time::{Duration, Instant};
+use toml::Value;
+
+const START_TOML_PROPERTY: &str = "start";
+const CONTINUATION_TOML_PROPERTY: &str = "continuation";
+const FLUSH_MS_TOML_PROPERTY: &str = "flush_ms";
+
+const DEFAULT_FLUSH_MS: u64 = 500;
+const CONTINUATION_PREFIXES: [&str; 3] = ["at ", "Caused by:", "..."];
+
+/// How lines are grouped into entries, configured under `[multiline]` in config.toml with
+/// either a `start` regex matching the first line of every entry, or `continuation =
+/// "indented"` for entries continued by lines that start with whitespace, `at `, `Caused
+/// by:` or `...` as in Java stack traces.
+#[derive(Clone)]
+pub struct EntryRules {
+    start: EntryStart,
+    flush_after: Duration,
+}
+
+#[derive(Clone)]
+enum EntryStart {
+    EveryLine,
+    Pattern(Regex),
+    Unindented,
+}
+
+/// Collects the lines of one source into entries. An entry is only complete once the next
+/// one starts, so the last one is given out after no line was added to it for a while.
+pub struct EntryGrouper {
+    rules: EntryRules,
+    pending: Option<String>,
+    last_line_at: Instant,
+}
+
+impl Default for EntryRules {
+    fn default() -> Self {
+        Self {
+            start: EntryStart::EveryLine,
+            flush_after: Duration::from_millis(DEFAULT_FLUSH_MS),
+        }
+    }
+}
+
+impl EntryRules {
+    pub fn new(multiline: Option<&Value>) -> Result<EntryRules, Error> {
+        let multiline = match multiline {
+            Some(multiline) if multiline.is_table() => multiline,
+            Some(_) => failure::bail!("multiline must be a table"),
+            None => return Ok(EntryRules::default()),
+        };
+
+        let start = match (
+            multiline.get(START_TOML_PROPERTY),
+            multiline.get(CONTINUATION_TOML_PROPERTY),
+        ) {
+            (Some(_), Some(_)) => failure::bail!("multiline takes either a start or continuation"),
+            (Some(Value::String(pattern)), None) => EntryStart::Pattern(
+                Regex::new(pattern)
+                    .map_err(|e| failure::format_err!("invalid multiline.start: {}", e))?,
+            ),
+            (None, Some(Value::String(continuation))) if continuation == "indented" => {
+                EntryStart::Unindented
+            }
+            (Some(_), None) => failure::bail!("multiline.start must be a string"),
+            (None, Some(_)) => failure::bail!("multiline.continuation must be \"indented\""),
+            (None, None) => failure::bail!("multiline needs a start or continuation"),
+        };
+
+        let flush_after = match multiline.get(FLUSH_MS_TOML_PROPERTY) {
+            Some(Value::Integer(ms)) if *ms > 0 => Duration::from_millis(*ms as u64),
+            Some(_) => failure::bail!("multiline.flush_ms must be a positive integer"),
+            None => Duration::from_millis(DEFAULT_FLUSH_MS),
+        };
+
+        Ok(EntryRules { start, flush_after })
+    }
+
+    pub fn starts_entry(&self, line: &str) -> bool {
+        match &self.start {
+            EntryStart::EveryLine => true,
+            EntryStart::Pattern(pattern) => pattern.is_match(line),
+            EntryStart::Unindented => {
+                !line.starts_with(char::is_whitespace)
+                    && !CONTINUATION_PREFIXES
+                        .iter()
+                        .any(|prefix| line.starts_with(prefix))
+            }
+        }
+    }
+}
+
+impl EntryGrouper {
+    pub fn new(rules: EntryRules) -> Self {
+        Self {
+            rules,
+            pending: None,
+            last_line_at: Instant::now(),
+        }
+    }
+
+    /// Adds a line, giving back the entry it completes when it starts a new one. Lines
+    /// continuing nothing, such as the first one read, are entries of their own.
+    pub fn push(&mut self, line: String) -> Option<String> {
+        self.last_line_at = Instant::now();
+
+        if let EntryStart::EveryLine = self.rules.start {
+            return Some(line);
+        }
+
+        match &mut self.pending {
+            Some(pending) if !self.rules.starts_entry(&line) => {
+                pending.push('\n');
+                pending.push_str(&line);
+
+                None
+            }
+            _ => self.pending.replace(line),
+        }
+    }
+
+    /// Gives back the pending entry once no line was added to it for the flush interval.
+    pub fn flush_expired(&mut self) -> Option<String> {
+        if self.last_line_at.elapsed() < self.rules.flush_after {
+            return None;
+        }
+
+        self.flush()
+    }
+
+    pub fn flush(&mut self) -> Option<String> {
+        self.pending.take()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn grouper(config: &str) -> EntryGrouper {
+        let config: Value = config.parse().unwrap();
+
+        EntryGrouper::new(EntryRules::new(Some(&config)).unwrap())
+    }
+
+    fn group(grouper: &mut EntryGrouper, lines: &[&str]) -> Vec<String> {
+        let mut entries: Vec<String> = lines
+            .iter()
+            .filter_map(|line| grouper.push(line.to_string()))
+            .collect();
+
+        entries.extend(grouper.flush());
+
+        entries
+    }
+
+    #[test]
+    fn indented_lines_continue_the_entry_before_them() {
+        let mut grouper = grouper(r#"continuation = "indented""#);
+        let entries = group(
+            &mut grouper,
+            &[
+                "\tat Orphan.run",
+                "ERROR failed",
+                "java.lang.IllegalStateException: closed",
+                "\tat Db.query",
+                "Caused by: java.io.IOException",
+                "... 3 more",
+                "INFO retrying",
+            ],
+        );
+
+        assert_eq!(
+            entries,
+            vec![
+                "\tat Orphan.run",
+                "ERROR failed",
+                "java.lang.IllegalStateException: closed\n\tat Db.query\nCaused by: java.io.IOException\n... 3 more",
+                "INFO retrying",
+            ]
+        );
+    }
+
+    #[test]
+    fn start_pattern_begins_every_entry() {
+        let mut grouper = grouper(r#"start = '^\d{4}-'"#);
+        let entries = group(
+            &mut grouper,
+            &["2024-01-01 first", "detail", "", "2024-01-02 second"],
+        );
+
+        assert_eq!(
+            entries,
+            vec!["2024-01-01 first\ndetail\n", "2024-01-02 second"]
+        );
+    }
+
+    #[test]
+    fn pending_entry_is_kept_until_the_flush_interval_passes() {
+        let mut grouper = grouper(
+            r#"start = "^S"
+flush_ms = 60000"#,
+        );
+
+        assert_eq!(grouper.push("S one".to_string()), None);
+        assert_eq!(grouper.flush_expired(), None);
+        assert_eq!(grouper.flush(), Some("S one".to_string()));
+        assert_eq!(grouper.flush(), None);
+    }
+
+    #[test]
+    fn every_line_is_an_entry_without_rules() {
+        let mut grouper = EntryGrouper::new(EntryRules::default());
+
+        assert_eq!(
+            grouper.push("  indented".to_string()),
+            Some("  indented".to_string())
+        );
+        assert_eq!(grouper.flush(), None);
+    }
+}
@@ -0,0 +1,493 @@
+use chrono::{DateTime, Datelike, Local, NaiveDateTime, NaiveTime, TimeZone};
+use failure::Error;
+use regex::Regex;
+use serde_json::Value as JsonValue;
+use std::fmt;
+use toml::Value;
+
+const FORMAT_TOML_PROPERTY: &str = "format";
+const PATTERN_TOML_PROPERTY: &str = "pattern";
+
+const TIMESTAMP_GROUP: &str = "timestamp";
+const LEVEL_GROUP: &str = "level";
+const LOGGER_GROUP: &str = "logger";
+const MESSAGE_GROUP: &str = "message";
+
+const LOGBACK_PATTERN: &str = r"^(?P<timestamp>(?:\d{4}-\d{2}-\d{2}[ T])?\d{2}:\d{2}:\d{2}(?:[.,]\d+)?)\s+(?:\[(?P<thread>[^\]]+)\]\s+)?(?P<level>[A-Za-z]+)\s+(?P<logger>\S+)\s+-\s(?P<message>.*)$";
+const LOG4J_PATTERN: &str = r"^(?P<timestamp>\d{4}-\d{2}-\d{2}[ T]\d{2}:\d{2}:\d{2}(?:[.,]\d+)?)\s+(?P<level>[A-Za-z]+)\s+\[(?P<thread>[^\]]+)\]\s+(?P<logger>\S+?):?\s+(?:-\s)?(?P<message>.*)$";
+const SYSLOG_PATTERN: &str = r"^(?P<timestamp>[A-Z][a-z]{2}\s+\d{1,2} \d{2}:\d{2}:\d{2})\s+(?P<host>\S+)\s+(?P<logger>[^:\[\s]+)(?:\[(?P<pid>\d+)\])?:\s?(?P<message>.*)$";
+const NGINX_PATTERN: &str = r#"^(?P<remote_addr>\S+) - (?P<remote_user>\S+) \[(?P<timestamp>[^\]]+)\] "(?P<message>[^"]*)" (?P<status>\d{3}) (?P<body_bytes_sent>\d+|-) "(?P<http_referer>[^"]*)" "(?P<http_user_agent>[^"]*)""#;
+
+const TIMESTAMP_FORMATS: [&str; 4] = [
+    "%Y-%m-%d %H:%M:%S%.f",
+    "%Y-%m-%dT%H:%M:%S%.f",
+    "%d/%b/%Y:%H:%M:%S %z",
+    "%Y %b %e %H:%M:%S",
+];
+const TIME_OF_DAY_FORMAT: &str = "%H:%M:%S%.f";
+
+/// Epoch values from this on are taken to be in milliseconds, seconds would only reach it
+/// in the year 5138.
+const EPOCH_MILLIS_THRESHOLD: i64 = 100_000_000_000;
+
+const JSON_TIMESTAMP_KEYS: [&str; 5] = ["timestamp", "@timestamp", "time", "ts", "datetime"];
+const JSON_LEVEL_KEYS: [&str; 4] = ["level", "severity", "lvl", "loglevel"];
+const JSON_LOGGER_KEYS: [&str; 4] = ["logger", "logger_name", "name", "component"];
+const JSON_MESSAGE_KEYS: [&str; 3] = ["message", "msg", "@message"];
+
+#[derive(Clone, Copy, PartialEq, PartialOrd)]
+pub enum Level {
+    Trace,
+    Debug,
+    Info,
+    Warn,
+    Error,
+    Fatal,
+}
+
+/// A log line split into its parts. Lines a parser does not recognise become records with
+/// the whole line as the message and the level guessed by `detect_level`.
+pub struct LogRecord {
+    pub timestamp: Option<String>,
+    pub level: Option<Level>,
+    pub logger: Option<String>,
+    pub message: String,
+    pub fields: Vec<(String, String)>,
+}
+
+/// Turns lines into records, configured under `[parser]` in config.toml with either a
+/// preset `format` or a `pattern` regex whose named groups `timestamp`, `level`, `logger`
+/// and `message` fill the record and any other named groups become fields.
+#[derive(Clone, Default)]
+pub enum LineParser {
+    #[default]
+    Plain,
+    Regex(Regex),
+    Json,
+}
+
+impl Level {
+    /// Accepts the usual names and abbreviations of log levels in any case.
+    pub fn parse(name: &str) -> Option<Level> {
+        let level = match name.to_uppercase().as_str() {
+            "TRACE" | "TRC" | "VERBOSE" => Level::Trace,
+            "DEBUG" | "DBG" => Level::Debug,
+            "INFO" | "INF" | "NOTICE" => Level::Info,
+            "WARN" | "WARNING" | "WRN" => Level::Warn,
+            "ERROR" | "ERR" => Level::Error,
+            "FATAL" | "FTL" | "CRITICAL" | "CRIT" | "PANIC" => Level::Fatal,
+            _ => return None,
+        };
+
+        Some(level)
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Level::Trace => "TRACE",
+            Level::Debug => "DEBUG",
+            Level::Info => "INFO",
+            Level::Warn => "WARN",
+            Level::Error => "ERROR",
+            Level::Fatal => "FATAL",
+        }
+    }
+}
+
+impl fmt::Display for Level {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+impl LogRecord {
+    pub fn plain(line: &str) -> LogRecord {
+        LogRecord {
+            timestamp: None,
+            level: detect_level(first_line(line)),
+            logger: None,
+            message: line.to_string(),
+            fields: vec![],
+        }
+    }
+
+    /// Looks up a field by name, where the names of the timestamp, level, logger and message
+    /// parts, or the JSON keys they are read from, refer to those parts.
+    pub fn field(&self, name: &str) -> Option<&str> {
+        if let Some((_, value)) = self.fields.iter().find(|(key, _)| key == name) {
+            return Some(value);
+        }
+
+        let is_one_of = |group: &str, keys: &[&str]| name == group || keys.contains(&name);
+
+        if is_one_of(TIMESTAMP_GROUP, &JSON_TIMESTAMP_KEYS) {
+            self.timestamp.as_deref()
+        } else if is_one_of(LEVEL_GROUP, &JSON_LEVEL_KEYS) {
+            self.level.map(Level::name)
+        } else if is_one_of(LOGGER_GROUP, &JSON_LOGGER_KEYS) {
+            self.logger.as_deref()
+        } else if is_one_of(MESSAGE_GROUP, &JSON_MESSAGE_KEYS) {
+            Some(&self.message)
+        } else {
+            None
+        }
+    }
+}
+
+impl LineParser {
+    pub fn new(parser: Option<&Value>) -> Result<LineParser, Error> {
+        let parser = match parser {
+            Some(parser) if parser.is_table() => parser,
+            Some(_) => failure::bail!("parser must be a table"),
+            None => return Ok(LineParser::Plain),
+        };
+
+        match (
+            parser.get(FORMAT_TOML_PROPERTY),
+            parser.get(PATTERN_TOML_PROPERTY),
+        ) {
+            (Some(_), Some(_)) => failure::bail!("parser takes either a format or a pattern"),
+            (Some(Value::String(format)), None) => LineParser::preset(format),
+            (None, Some(Value::String(pattern))) => LineParser::pattern(pattern),
+            (Some(_), None) => failure::bail!("parser.format must be a string"),
+            (None, Some(_)) => failure::bail!("parser.pattern must be a string"),
+            (None, None) => Ok(LineParser::Plain),
+        }
+    }
+
+    pub fn preset(format: &str) -> Result<LineParser, Error> {
+        let pattern = match format {
+            "plain" => return Ok(LineParser::Plain),
+            "json" => return Ok(LineParser::Json),
+            "logback" => LOGBACK_PATTERN,
+            "log4j" => LOG4J_PATTERN,
+            "syslog" => SYSLOG_PATTERN,
+            "nginx" => NGINX_PATTERN,
+            _ => failure::bail!(
+                "unknown format {}, expected plain, logback, log4j, syslog, nginx or json",
+                format
+            ),
+        };
+
+        LineParser::pattern(pattern)
+    }
+
+    pub fn pattern(pattern: &str) -> Result<LineParser, Error> {
+        let regex = Regex::new(pattern)
+            .map_err(|e| failure::format_err!("invalid parser pattern: {}", e))?;
+
+        if !regex.capture_names().any(|name| name.is_some()) {
+            failure::bail!("parser pattern has no named groups");
+        }
+
+        Ok(LineParser::Regex(regex))
+    }
+
+    pub fn is_plain(&self) -> bool {
+        matches!(self, LineParser::Plain)
+    }
+
+    pub fn parse(&self, line: &str) -> LogRecord {
+        self.parse_structured(line)
+            .unwrap_or_else(|| LogRecord::plain(line))
+    }
+
+    /// Like `parse`, but gives nothing for lines the parser does not recognise. Entries of
+    /// several lines are parsed by their first line, the others are added to the message.
+    pub fn parse_structured(&self, line: &str) -> Option<LogRecord> {
+        let (line, continuation) = match line.find('\n') {
+            Some(end) => (&line[..end], Some(&line[end..])),
+            None => (line, None),
+        };
+
+        let mut record = match self {
+            LineParser::Plain => None,
+            LineParser::Regex(regex) => parse_captures(regex, line),
+            LineParser::Json => parse_json(line),
+        }?;
+
+        if let Some(continuation) = continuation {
+            record.message.push_str(continuation);
+        }
+
+        Some(record)
+    }
+}
+
+/// Finds the log level of a line as the first token that is a known level name, so level
+/// names appearing later in the message payload are ignored.
+pub fn detect_level(message: &str) -> Option<Level> {
+    message
+        .split_whitespace()
+        .map(|token| token.trim_matches(|c| c == '[' || c == ']' || c == ':'))
+        .find_map(Level::parse)
+}
+
+/// Reads the timestamps of the preset formats and RFC 3339 ones as the time written in them,
+/// ignoring the offset. Syslog timestamps have no year, so they are taken to be this year's,
+/// and bare times of day are taken to be today's. Epoch seconds or milliseconds, as JSON
+/// logs often have, are read as local time.
+pub fn parse_timestamp(timestamp: &str) -> Option<NaiveDateTime> {
+    let timestamp = timestamp.trim().replace(',', ".");
+
+    if let Some(date_time) = parse_epoch(&timestamp) {
+        return Some(date_time);
+    }
+
+    if let Ok(date_time) = DateTime::parse_from_rfc3339(&timestamp) {
+        return Some(date_time.naive_local());
+    }
+
+    let with_year = format!("{} {}", Local::now().year(), timestamp);
+
+    TIMESTAMP_FORMATS
+        .iter()
+        .find_map(|format| {
+            NaiveDateTime::parse_from_str(&timestamp, format)
+                .or_else(|_| NaiveDateTime::parse_from_str(&with_year, format))
+                .ok()
+        })
+        .or_else(|| {
+            NaiveTime::parse_from_str(&timestamp, TIME_OF_DAY_FORMAT)
+                .ok()
+                .map(|time| Local::today().naive_local().and_time(time))
+        })
+}
+
+fn parse_epoch(timestamp: &str) -> Option<NaiveDateTime> {
+    let (whole, fraction) = match timestamp.find('.') {
+        Some(dot) => (&timestamp[..dot], &timestamp[dot + 1..]),
+        None => (timestamp, ""),
+    };
+    let is_digits = |text: &str| text.bytes().all(|byte| byte.is_ascii_digit());
+
+    if whole.is_empty() || !is_digits(whole) || !is_digits(fraction) {
+        return None;
+    }
+
+    let whole = whole.parse::<i64>().ok()?;
+    let fraction = &fraction[..fraction.len().min(9)];
+    let fraction_nanoseconds = format!("{:0<9}", fraction).parse::<i64>().ok()?;
+
+    let (seconds, nanoseconds) = if whole >= EPOCH_MILLIS_THRESHOLD {
+        (
+            whole / 1000,
+            whole % 1000 * 1_000_000 + fraction_nanoseconds / 1000,
+        )
+    } else {
+        (whole, fraction_nanoseconds)
+    };
+
+    Local
+        .timestamp_opt(seconds, nanoseconds as u32)
+        .single()
+        .map(|date_time| date_time.naive_local())
+}
+
+fn first_line(text: &str) -> &str {
+    text.lines().next().unwrap_or("")
+}
+
+fn parse_captures(regex: &Regex, line: &str) -> Option<LogRecord> {
+    let captures = regex.captures(line)?;
+    let mut record = LogRecord {
+        timestamp: None,
+        level: None,
+        logger: None,
+        message: line.to_string(),
+        fields: vec![],
+    };
+
+    for name in regex.capture_names().flatten() {
+        let value = match captures.name(name) {
+            Some(value) => value.as_str(),
+            None => continue,
+        };
+
+        match name {
+            TIMESTAMP_GROUP => record.timestamp = Some(value.to_string()),
+            LEVEL_GROUP => {
+                record.level = Level::parse(value);
+
+                if record.level.is_none() {
+                    record.fields.push((name.to_string(), value.to_string()));
+                }
+            }
+            LOGGER_GROUP => record.logger = Some(value.to_string()),
+            MESSAGE_GROUP => record.message = value.to_string(),
+            _ => record.fields.push((name.to_string(), value.to_string())),
+        }
+    }
+
+    Some(record)
+}
+
+fn parse_json(line: &str) -> Option<LogRecord> {
+    let object = match serde_json::from_str::<JsonValue>(line.trim()).ok()? {
+        JsonValue::Object(object) => object,
+        _ => return None,
+    };
+
+    let mut record = LogRecord {
+        timestamp: None,
+        level: None,
+        logger: None,
+        message: String::new(),
+        fields: vec![],
+    };
+
+    for (key, value) in object {
+        let value = json_to_string(&value);
+        let key_is = |keys: &[&str]| keys.iter().any(|known| key.eq_ignore_ascii_case(known));
+
+        if record.timestamp.is_none() && key_is(&JSON_TIMESTAMP_KEYS) {
+            record.timestamp = Some(value);
+        } else if record.level.is_none()
+            && key_is(&JSON_LEVEL_KEYS)
+            && Level::parse(&value).is_some()
+        {
+            record.level = Level::parse(&value);
+        } else if record.logger.is_none() && key_is(&JSON_LOGGER_KEYS) {
+            record.logger = Some(value);
+        } else if record.message.is_empty() && key_is(&JSON_MESSAGE_KEYS) {
+            record.message = value;
+        } else {
+            record.fields.push((key, value));
+        }
+    }
+
+    Some(record)
+}
+
+/// Strings are shown without quotes, anything else as compact JSON.
+pub fn json_to_string(value: &JsonValue) -> String {
+    match value {
+        JsonValue::String(text) => text.clone(),
+        value => value.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveDate;
+
+    fn date_time(date: (i32, u32, u32), time: (u32, u32, u32, u32)) -> NaiveDateTime {
+        NaiveDate::from_ymd(date.0, date.1, date.2).and_hms_milli(time.0, time.1, time.2, time.3)
+    }
+
+    #[test]
+    fn logback_lines_are_parsed_with_or_without_a_date() {
+        let parser = LineParser::preset("logback").unwrap();
+
+        let record = parser
+            .parse_structured("2024-01-02 10:11:12,345 [main] INFO  com.app.Foo - hello")
+            .unwrap();
+        assert_eq!(record.timestamp.as_deref(), Some("2024-01-02 10:11:12,345"));
+        assert!(record.level == Some(Level::Info));
+        assert_eq!(record.logger.as_deref(), Some("com.app.Foo"));
+        assert_eq!(record.message, "hello");
+        assert_eq!(record.field("thread"), Some("main"));
+
+        let record = parser
+            .parse_structured("10:11:12.345 [main] INFO  com.app.Foo - hello")
+            .unwrap();
+        assert_eq!(record.timestamp.as_deref(), Some("10:11:12.345"));
+        assert_eq!(record.message, "hello");
+
+        assert!(parser.parse_structured("hello").is_none());
+    }
+
+    #[test]
+    fn preset_lines_fill_the_record() {
+        let record = LineParser::preset("log4j")
+            .unwrap()
+            .parse_structured("2024-01-02 10:11:12,345 WARN  [worker-1] com.app.Bar: disk low")
+            .unwrap();
+        assert!(record.level == Some(Level::Warn));
+        assert_eq!(record.logger.as_deref(), Some("com.app.Bar"));
+        assert_eq!(record.message, "disk low");
+
+        let record = LineParser::preset("syslog")
+            .unwrap()
+            .parse_structured("Jan  2 10:11:12 host sshd[42]: Accepted key")
+            .unwrap();
+        assert_eq!(record.timestamp.as_deref(), Some("Jan  2 10:11:12"));
+        assert_eq!(record.logger.as_deref(), Some("sshd"));
+        assert_eq!(record.field("pid"), Some("42"));
+        assert_eq!(record.message, "Accepted key");
+
+        let record = LineParser::preset("nginx")
+            .unwrap()
+            .parse_structured(
+                r#"10.0.0.1 - - [02/Jan/2024:10:11:12 +0100] "GET / HTTP/1.1" 200 512 "-" "curl/8.0""#,
+            )
+            .unwrap();
+        assert_eq!(record.message, "GET / HTTP/1.1");
+        assert_eq!(record.field("status"), Some("200"));
+        assert_eq!(record.field("http_user_agent"), Some("curl/8.0"));
+    }
+
+    #[test]
+    fn json_lines_fill_the_record_and_keep_other_keys_as_fields() {
+        let record = LineParser::preset("json")
+            .unwrap()
+            .parse_structured(r#"{"ts":1700000000,"level":"error","msg":"boom","user":"ann"}"#)
+            .unwrap();
+
+        assert_eq!(record.timestamp.as_deref(), Some("1700000000"));
+        assert!(record.level == Some(Level::Error));
+        assert_eq!(record.message, "boom");
+        assert_eq!(record.field("user"), Some("ann"));
+        assert!(LineParser::preset("json")
+            .unwrap()
+            .parse_structured("not json")
+            .is_none());
+    }
+
+    #[test]
+    fn unknown_presets_are_rejected() {
+        let error = LineParser::preset("apache").err().unwrap().to_string();
+
+        assert!(error.starts_with("unknown format apache"), "{}", error);
+    }
+
+    #[test]
+    fn timestamps_of_the_presets_are_read_as_written() {
+        let expected = date_time((2024, 1, 2), (10, 11, 12, 345));
+
+        assert_eq!(parse_timestamp("2024-01-02 10:11:12,345"), Some(expected));
+        assert_eq!(parse_timestamp("2024-01-02T10:11:12.345"), Some(expected));
+        assert_eq!(
+            parse_timestamp("2024-01-02T10:11:12.345+05:00"),
+            Some(expected)
+        );
+        assert_eq!(
+            parse_timestamp("02/Jan/2024:10:11:12 +0100"),
+            Some(date_time((2024, 1, 2), (10, 11, 12, 0)))
+        );
+        assert_eq!(
+            parse_timestamp("Jan  2 10:11:12"),
+            Some(date_time((Local::now().year(), 1, 2), (10, 11, 12, 0)))
+        );
+        assert_eq!(
+            parse_timestamp("10:11:12.345"),
+            Some(Local::today().naive_local().and_hms_milli(10, 11, 12, 345))
+        );
+        assert_eq!(parse_timestamp("hello"), None);
+    }
+
+    #[test]
+    fn epoch_timestamps_are_read_in_seconds_or_milliseconds() {
+        let expected = Local.timestamp(1_700_000_000, 123_000_000).naive_local();
+
+        assert_eq!(parse_timestamp("1700000000.123"), Some(expected));
+        assert_eq!(parse_timestamp("1700000000123"), Some(expected));
+        assert_eq!(
+            parse_timestamp("1700000000"),
+            Some(Local.timestamp(1_700_000_000, 0).naive_local())
+        );
+        assert_eq!(parse_timestamp("-1700000000"), None);
+        assert_eq!(parse_timestamp("99999999999999999999"), None);
+    }
+}
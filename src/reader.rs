@@ -0,0 +1,1181 @@
+use crate::classifier::{Classifier, SharedClassifier};
+use crate::multiline::EntryRules;
+use crate::Notifier;
+use bzip2::read::MultiBzDecoder;
+use failure::Error;
+use flate2::read::MultiGzDecoder;
+use memmap2::Mmap;
+use std::borrow::Cow;
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader, Read, Seek, SeekFrom};
+use std::os::unix::fs::MetadataExt;
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, Stdio};
+use std::sync::{mpsc, Arc};
+use std::thread;
+use tui::style::Style;
+use xz2::read::XzDecoder;
+
+const CHUNK_SIZE: u64 = 64 * 1024;
+const INDEX_BATCH_SIZE: usize = 10_000;
+const MAPPED_ENTRIES_PER_READ: usize = 50_000;
+
+pub enum LogEvent {
+    Line(String),
+    Rotated,
+    Truncated,
+    Ended,
+    Failed(String),
+    Continued { from: String, to: String },
+    Mapped(MappedEntry),
+}
+
+pub enum LogSource {
+    File(LogReader),
+    Archive(PipeReader),
+    Pipe(PipeReader),
+    Series(SeriesReader),
+    Mapped(MappedReader),
+}
+
+/// Formats recognized by the magic bytes at the start of a file.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Compression {
+    Gzip,
+    Zstd,
+    Bzip2,
+    Xz,
+}
+
+impl Compression {
+    const MAGIC_BYTES: [(Compression, &'static [u8]); 4] = [
+        (Compression::Gzip, &[0x1f, 0x8b]),
+        (Compression::Zstd, &[0x28, 0xb5, 0x2f, 0xfd]),
+        (Compression::Bzip2, b"BZh"),
+        (Compression::Xz, &[0xfd, b'7', b'z', b'X', b'Z', 0x00]),
+    ];
+
+    pub fn detect(file: &mut File) -> Result<Option<Compression>, Error> {
+        let mut header = [0; 6];
+        let mut length = 0;
+
+        while length < header.len() {
+            match file.read(&mut header[length..])? {
+                0 => break,
+                read_bytes => length += read_bytes,
+            }
+        }
+
+        file.seek(SeekFrom::Start(0))?;
+
+        Ok(Compression::MAGIC_BYTES
+            .iter()
+            .find(|(_, magic)| header[..length].starts_with(magic))
+            .map(|(compression, _)| *compression))
+    }
+
+    fn decoder(self, file: File) -> Result<Box<dyn Read + Send>, Error> {
+        Ok(match self {
+            Compression::Gzip => Box::new(MultiGzDecoder::new(file)),
+            Compression::Zstd => Box::new(zstd::Decoder::new(file)?),
+            Compression::Bzip2 => Box::new(MultiBzDecoder::new(file)),
+            Compression::Xz => Box::new(XzDecoder::new_multi_decoder(file)),
+        })
+    }
+}
+
+pub struct LogReader {
+    path: PathBuf,
+    reader: BufReader<File>,
+    inode: u64,
+    position: u64,
+    start_position: u64,
+    partial_line: Vec<u8>,
+    follow: bool,
+    is_finished: bool,
+}
+
+impl LogReader {
+    /// Opens the file at its start, or `tail_lines` lines or the lines in the last
+    /// `tail_bytes` bytes before its end. Without `follow` the file is read once up to its
+    /// current end.
+    pub fn open<P: AsRef<Path>>(
+        path: P,
+        tail_lines: Option<usize>,
+        tail_bytes: Option<u64>,
+        follow: bool,
+    ) -> Result<LogReader, Error> {
+        let path = path.as_ref().to_path_buf();
+        let mut file = File::open(&path)
+            .map_err(|e| failure::format_err!("Failed opening {}: {}", path.display(), e))?;
+        let inode = file.metadata()?.ino();
+
+        let position = match (tail_lines, tail_bytes) {
+            (Some(lines), _) => find_tail_start(&mut file, lines)?,
+            (None, Some(bytes)) => {
+                let length = file.metadata()?.len();
+
+                find_line_start(&mut file, length.saturating_sub(bytes))?
+            }
+            (None, None) => 0,
+        };
+
+        file.seek(SeekFrom::Start(position))?;
+
+        Ok(LogReader {
+            path,
+            reader: BufReader::new(file),
+            inode,
+            position,
+            start_position: position,
+            partial_line: vec![],
+            follow,
+            is_finished: false,
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Reads the lines in about `bytes` bytes before where reading started, or what is left
+    /// of them, oldest first. There are none once the start of the file was reached or the
+    /// file was replaced or truncated.
+    pub fn read_older_lines(&mut self, bytes: u64) -> Result<Vec<String>, Error> {
+        if self.start_position == 0 {
+            return Ok(vec![]);
+        }
+
+        let mut file = match File::open(&self.path) {
+            Ok(file) => file,
+            Err(error) if error.kind() == io::ErrorKind::NotFound => {
+                self.start_position = 0;
+
+                return Ok(vec![]);
+            }
+            Err(error) => return Err(error.into()),
+        };
+
+        if file.metadata()?.ino() != self.inode {
+            self.start_position = 0;
+
+            return Ok(vec![]);
+        }
+
+        let chunk_start = find_line_start(&mut file, self.start_position.saturating_sub(bytes))?;
+        let mut chunk = vec![0; (self.start_position - chunk_start) as usize];
+
+        // What was before the start is gone, following the file notices the truncation
+        if !read_chunk(&mut file, chunk_start, &mut chunk)? {
+            self.start_position = 0;
+
+            return Ok(vec![]);
+        }
+
+        self.start_position = chunk_start;
+
+        Ok(chunk
+            .split_inclusive(|byte| *byte == b'\n')
+            .map(decode_line)
+            .collect())
+    }
+
+    /// Tells whether a file opened without `follow` was read to its end.
+    pub fn is_finished(&self) -> bool {
+        self.is_finished
+    }
+
+    /// Reads every complete line appended since the last call. Follows the path the
+    /// same way `tail -F` does: a new inode means the file was rotated and a size
+    /// smaller than what was already read means it was truncated, in both cases
+    /// reading continues from the start of the current file. A file that cannot be read
+    /// anymore ends with a `Failed` event and is not read again.
+    pub fn read_events(&mut self) -> Vec<LogEvent> {
+        let mut events = vec![];
+
+        if let Err(error) = self.read_new_events(&mut events) {
+            self.flush_partial_line(&mut events);
+            events.push(LogEvent::Failed(error.to_string()));
+            self.follow = false;
+            self.is_finished = true;
+        }
+
+        events
+    }
+
+    fn read_new_events(&mut self, events: &mut Vec<LogEvent>) -> Result<(), Error> {
+        if !self.follow {
+            if !self.is_finished {
+                self.read_available_lines(events)?;
+                self.flush_partial_line(events);
+                self.is_finished = true;
+            }
+
+            return Ok(());
+        }
+
+        loop {
+            self.read_available_lines(events)?;
+
+            let metadata = match fs::metadata(&self.path) {
+                Ok(metadata) => metadata,
+                Err(_) => break,
+            };
+
+            if metadata.ino() != self.inode {
+                self.read_available_lines(events)?;
+                self.flush_partial_line(events);
+
+                match self.reopen() {
+                    Ok(()) => events.push(LogEvent::Rotated),
+                    // Moved away again before it could be opened, the next read tries again
+                    Err(error) if error.kind() == io::ErrorKind::NotFound => break,
+                    Err(error) => return Err(error.into()),
+                }
+            } else if metadata.len() < self.position {
+                self.flush_partial_line(events);
+                self.reader.seek(SeekFrom::Start(0))?;
+                self.position = 0;
+                self.start_position = 0;
+                events.push(LogEvent::Truncated);
+            } else {
+                break;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn read_available_lines(&mut self, events: &mut Vec<LogEvent>) -> Result<(), Error> {
+        loop {
+            let read_bytes = self.reader.read_until(b'\n', &mut self.partial_line)?;
+
+            if read_bytes == 0 {
+                return Ok(());
+            }
+
+            self.position += read_bytes as u64;
+
+            if !self.partial_line.ends_with(b"\n") {
+                return Ok(());
+            }
+
+            events.push(LogEvent::Line(decode_line(&self.partial_line)));
+            self.partial_line.clear();
+        }
+    }
+
+    fn flush_partial_line(&mut self, events: &mut Vec<LogEvent>) {
+        if !self.partial_line.is_empty() {
+            events.push(LogEvent::Line(decode_line(&self.partial_line)));
+            self.partial_line.clear();
+        }
+    }
+
+    fn reopen(&mut self) -> io::Result<()> {
+        let file = File::open(&self.path)?;
+
+        self.inode = file.metadata()?.ino();
+        self.reader = BufReader::new(file);
+        self.position = 0;
+        self.start_position = 0;
+
+        Ok(())
+    }
+}
+
+/// Reads lines from stdin or a spawned command on background threads, since a pipe
+/// blocks until the writer produces more output.
+pub struct PipeReader {
+    name: String,
+    rx: mpsc::Receiver<Option<LogEvent>>,
+    open_stream_count: usize,
+    child: Option<Child>,
+}
+
+impl PipeReader {
+    pub fn stdin(notifier: Notifier) -> PipeReader {
+        let (tx, rx) = mpsc::channel();
+
+        spawn_line_reader(io::stdin(), tx, notifier);
+
+        PipeReader {
+            name: "stdin".to_string(),
+            rx,
+            open_stream_count: 1,
+            child: None,
+        }
+    }
+
+    pub fn command(command: &str, notifier: Notifier) -> Result<PipeReader, Error> {
+        let (tx, rx) = mpsc::channel();
+
+        let mut child = Command::new("sh")
+            .arg("-c")
+            .arg(command)
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(|e| failure::format_err!("Failed running command {}: {}", command, e))?;
+
+        if let Some(stdout) = child.stdout.take() {
+            spawn_line_reader(stdout, tx.clone(), notifier.clone());
+        }
+
+        if let Some(stderr) = child.stderr.take() {
+            spawn_line_reader(stderr, tx, notifier);
+        }
+
+        Ok(PipeReader {
+            name: command.to_string(),
+            rx,
+            open_stream_count: 2,
+            child: Some(child),
+        })
+    }
+
+    /// Decompresses the file on a background thread, reading it once from the start
+    /// since the end of a compressed file is only found by decompressing all of it.
+    pub fn archive<P: AsRef<Path>>(
+        path: P,
+        compression: Compression,
+        notifier: Notifier,
+    ) -> Result<PipeReader, Error> {
+        let path = path.as_ref();
+        let (tx, rx) = mpsc::channel();
+
+        let file = File::open(path)
+            .map_err(|e| failure::format_err!("Failed opening {}: {}", path.display(), e))?;
+
+        spawn_line_reader(compression.decoder(file)?, tx, notifier);
+
+        Ok(PipeReader {
+            name: path.display().to_string(),
+            rx,
+            open_stream_count: 1,
+            child: None,
+        })
+    }
+
+    pub fn read_events(&mut self) -> Vec<LogEvent> {
+        let mut events = vec![];
+
+        while let Ok(event) = self.rx.try_recv() {
+            match event {
+                Some(event) => events.push(event),
+                None => {
+                    self.open_stream_count -= 1;
+
+                    if self.open_stream_count == 0 {
+                        events.push(LogEvent::Ended);
+                    }
+                }
+            }
+        }
+
+        events
+    }
+}
+
+/// A log file read after its rotated predecessors, `server.log.2.gz`, `server.log.1` and so
+/// on, oldest first, with an event between files marking where one ends and the next starts.
+pub struct SeriesReader {
+    path: PathBuf,
+    current: Box<LogSource>,
+    current_path: PathBuf,
+    remaining: Vec<PathBuf>,
+    follow: bool,
+    notifier: Notifier,
+}
+
+impl SeriesReader {
+    pub fn open<P: AsRef<Path>>(
+        path: P,
+        follow: bool,
+        notifier: Notifier,
+    ) -> Result<SeriesReader, Error> {
+        let path = path.as_ref().to_path_buf();
+        let mut remaining = rotated_paths(&path)?;
+        remaining.push(path.clone());
+
+        let current_path = remaining.remove(0);
+        let current = open_series_file(&current_path, remaining.is_empty(), follow, &notifier)?;
+
+        Ok(SeriesReader {
+            path,
+            current: Box::new(current),
+            current_path,
+            remaining,
+            follow,
+            notifier,
+        })
+    }
+
+    /// Reads the current file, moving on to the next one once it was read to its end. Only
+    /// the live file is followed and tells when it ended.
+    /// Reads the current file, moving on to the next one once it is read. A file that
+    /// cannot be opened, such as one rotated away meanwhile, is skipped with a `Failed` event.
+    pub fn read_events(&mut self) -> Vec<LogEvent> {
+        let mut events = vec![];
+
+        loop {
+            let is_live = self.remaining.is_empty();
+
+            events.extend(
+                self.current
+                    .read_events()
+                    .into_iter()
+                    .filter(|event| is_live || !matches!(event, LogEvent::Ended)),
+            );
+
+            if is_live || !self.current.is_finished() {
+                return events;
+            }
+
+            let next_path = self.remaining.remove(0);
+            let next = match open_series_file(
+                &next_path,
+                self.remaining.is_empty(),
+                self.follow,
+                &self.notifier,
+            ) {
+                Ok(next) => next,
+                Err(error) => {
+                    events.push(LogEvent::Failed(error.to_string()));
+
+                    continue;
+                }
+            };
+
+            events.push(LogEvent::Continued {
+                from: file_name(&self.current_path),
+                to: file_name(&next_path),
+            });
+
+            *self.current = next;
+            self.current_path = next_path;
+        }
+    }
+}
+
+/// Opens one file of a rotated series from its start, the config refuses a tail together
+/// with rotated files as the series is read as one timeline from its oldest file.
+fn open_series_file(
+    path: &Path,
+    is_live: bool,
+    follow: bool,
+    notifier: &Notifier,
+) -> Result<LogSource, Error> {
+    LogSource::open(path, None, None, is_live && follow, notifier.clone())
+}
+
+/// Finds the rotated copies of a log file next to it, named after it with a number and
+/// optionally an extension such as `.gz`, the highest numbered and so oldest one first.
+fn rotated_paths(path: &Path) -> Result<Vec<PathBuf>, Error> {
+    let name = file_name(path);
+    let directory = match path.parent() {
+        Some(parent) if parent != Path::new("") => parent,
+        _ => Path::new("."),
+    };
+    let prefix = format!("{}.", name);
+
+    let mut rotated: Vec<(u32, PathBuf)> = fs::read_dir(directory)
+        .map_err(|e| failure::format_err!("Failed reading {}: {}", directory.display(), e))?
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| {
+            let entry_name = entry.file_name().to_string_lossy().to_string();
+            let suffix = entry_name.strip_prefix(&prefix)?;
+            let number = suffix.split('.').next()?.parse().ok()?;
+
+            Some((number, entry.path()))
+        })
+        .collect();
+
+    rotated.sort_by(|(number, path), (other_number, other_path)| {
+        other_number.cmp(number).then_with(|| path.cmp(other_path))
+    });
+
+    Ok(rotated.into_iter().map(|(_, path)| path).collect())
+}
+
+fn file_name(path: &Path) -> String {
+    match path.file_name() {
+        Some(file_name) => file_name.to_string_lossy().to_string(),
+        None => path.display().to_string(),
+    }
+}
+
+/// A file read in place through a memory map for looking at it without following it. A
+/// background thread indexes where its entries start and classifies them, so only the
+/// entries being shown or searched are ever copied out of the file.
+pub struct MappedReader {
+    path: PathBuf,
+    map: Arc<Mmap>,
+    rules: EntryRules,
+    indexing: Option<Indexing>,
+    notifier: Notifier,
+}
+
+/// An entry of a file read in place as the indexer thread found it: where it ends, which
+/// is where the next one starts, along with its style and the tabs it goes to.
+pub struct MappedEntry {
+    pub end: u64,
+    pub style: Style,
+    pub tabs: Vec<usize>,
+}
+
+/// Entries following each other from `start`, with the classifier they were classified with.
+struct MappedBatch {
+    start: u64,
+    entries: Vec<MappedEntry>,
+    classifier: Arc<Classifier>,
+}
+
+struct Indexing {
+    rx: mpsc::Receiver<MappedBatch>,
+    pending: Option<MappedBatch>,
+    source_label: String,
+    classifier: SharedClassifier,
+}
+
+impl MappedReader {
+    pub fn open<P: AsRef<Path>>(
+        path: P,
+        rules: EntryRules,
+        notifier: Notifier,
+    ) -> Result<MappedReader, Error> {
+        let path = path.as_ref().to_path_buf();
+        let file = File::open(&path)
+            .map_err(|e| failure::format_err!("Failed opening {}: {}", path.display(), e))?;
+
+        // Reading a mapped page past the end of a truncated file kills the process with
+        // SIGBUS, there is no error to handle, so static mode assumes files only grow
+        let map = Arc::new(
+            unsafe { Mmap::map(&file) }
+                .map_err(|e| failure::format_err!("Failed mapping {}: {}", path.display(), e))?,
+        );
+
+        Ok(MappedReader {
+            path,
+            map,
+            rules,
+            indexing: None,
+            notifier,
+        })
+    }
+
+    pub fn map(&self) -> Arc<Mmap> {
+        Arc::clone(&self.map)
+    }
+
+    /// Starts the indexer thread once the label filters match the source by is known.
+    pub fn start_indexing(&mut self, source_label: &str, classifier: &SharedClassifier) {
+        let (tx, rx) = mpsc::channel();
+
+        spawn_indexer(
+            Arc::clone(&self.map),
+            self.rules.clone(),
+            source_label.to_string(),
+            Arc::clone(classifier),
+            tx,
+            self.notifier.clone(),
+        );
+
+        self.indexing = Some(Indexing {
+            rx,
+            pending: None,
+            source_label: source_label.to_string(),
+            classifier: Arc::clone(classifier),
+        });
+    }
+
+    /// Gives the entries indexed so far, a limited number at a time so the screen keeps
+    /// being redrawn while a large file is read, asking to be called again for the rest.
+    pub fn read_events(&mut self) -> Vec<LogEvent> {
+        let indexing = match &mut self.indexing {
+            Some(indexing) => indexing,
+            None => return vec![],
+        };
+        let mut events = vec![];
+
+        while events.len() < MAPPED_ENTRIES_PER_READ {
+            let batch = match indexing.pending.take() {
+                Some(batch) => batch,
+                None => match indexing.rx.try_recv() {
+                    Ok(batch) => batch,
+                    Err(_) => break,
+                },
+            };
+            let mut batch =
+                batch.classify_again(&self.map, &indexing.source_label, &indexing.classifier);
+
+            let count = batch
+                .entries
+                .len()
+                .min(MAPPED_ENTRIES_PER_READ - events.len());
+            let rest = batch.entries.split_off(count);
+            let entries = std::mem::replace(&mut batch.entries, rest);
+
+            if !batch.entries.is_empty() {
+                batch.start = entries.last().map_or(batch.start, |entry| entry.end);
+                indexing.pending = Some(batch);
+            }
+
+            events.extend(entries.into_iter().map(LogEvent::Mapped));
+        }
+
+        // Batches still in the channel were announced already, so ask for another read
+        if events.len() == MAPPED_ENTRIES_PER_READ {
+            self.notifier.notify();
+        }
+
+        events
+    }
+}
+
+impl MappedBatch {
+    fn new(start: u64, classifier: &SharedClassifier) -> Self {
+        Self {
+            start,
+            entries: vec![],
+            classifier: Arc::clone(&classifier.read().unwrap()),
+        }
+    }
+
+    fn end(&self) -> u64 {
+        self.entries.last().map_or(self.start, |entry| entry.end)
+    }
+
+    fn push(&mut self, bytes: &[u8], end: u64, source_label: &str) {
+        let (style, _, tabs) = self.classifier.classify(source_label, &decode_entry(bytes));
+
+        self.entries.push(MappedEntry { end, style, tabs });
+    }
+
+    /// Classifies the entries again when the tabs changed since the indexer thread did.
+    fn classify_again(
+        mut self,
+        map: &[u8],
+        source_label: &str,
+        classifier: &SharedClassifier,
+    ) -> Self {
+        let classifier = Arc::clone(&classifier.read().unwrap());
+
+        if Arc::ptr_eq(&classifier, &self.classifier) {
+            return self;
+        }
+
+        let mut start = self.start;
+
+        for entry in &mut self.entries {
+            let text = decode_entry(&map[start as usize..entry.end as usize]);
+            let (style, _, tabs) = classifier.classify(source_label, &text);
+
+            entry.style = style;
+            entry.tabs = tabs;
+            start = entry.end;
+        }
+
+        self.classifier = classifier;
+
+        self
+    }
+}
+
+/// Walks the mapped file line by line, sending its entries in batches classified with the
+/// classifier shared at the time. Lines continuing an entry are part of it.
+fn spawn_indexer(
+    map: Arc<Mmap>,
+    rules: EntryRules,
+    source_label: String,
+    classifier: SharedClassifier,
+    tx: mpsc::Sender<MappedBatch>,
+    notifier: Notifier,
+) {
+    thread::spawn(move || {
+        let mut batch = MappedBatch::new(0, &classifier);
+        let mut entry_start: Option<usize> = None;
+        let mut line_start = 0;
+
+        while line_start < map.len() {
+            let line_break = map[line_start..]
+                .iter()
+                .position(|byte| *byte == b'\n')
+                .map(|index| line_start + index);
+            let mut line_end = line_break.unwrap_or_else(|| map.len());
+
+            if line_end > line_start && map[line_end - 1] == b'\r' {
+                line_end -= 1;
+            }
+
+            let starts_entry = entry_start.is_none()
+                || rules.starts_entry(&String::from_utf8_lossy(&map[line_start..line_end]));
+
+            if starts_entry {
+                if let Some(start) = entry_start.replace(line_start) {
+                    batch.push(&map[start..line_start], line_start as u64, &source_label);
+                }
+            }
+
+            if batch.entries.len() >= INDEX_BATCH_SIZE {
+                let next = MappedBatch::new(batch.end(), &classifier);
+
+                if tx.send(std::mem::replace(&mut batch, next)).is_err() {
+                    return;
+                }
+
+                notifier.notify();
+            }
+
+            line_start = match line_break {
+                Some(line_break) => line_break + 1,
+                None => map.len(),
+            };
+        }
+
+        if let Some(start) = entry_start {
+            batch.push(&map[start..], map.len() as u64, &source_label);
+        }
+
+        if tx.send(batch).is_ok() {
+            notifier.notify();
+        }
+    });
+}
+
+impl Drop for PipeReader {
+    fn drop(&mut self) {
+        if let Some(child) = self.child.as_mut() {
+            child.kill().ok();
+            child.wait().ok();
+        }
+    }
+}
+
+impl LogSource {
+    /// Opens a log file, or a compressed one in static mode whatever `follow` says, as
+    /// nothing is appended to archives.
+    pub fn open<P: AsRef<Path>>(
+        path: P,
+        tail_lines: Option<usize>,
+        tail_bytes: Option<u64>,
+        follow: bool,
+        notifier: Notifier,
+    ) -> Result<LogSource, Error> {
+        let path = path.as_ref();
+        let mut file = File::open(path)
+            .map_err(|e| failure::format_err!("Failed opening {}: {}", path.display(), e))?;
+
+        match Compression::detect(&mut file)? {
+            Some(compression) => Ok(LogSource::Archive(PipeReader::archive(
+                path,
+                compression,
+                notifier,
+            )?)),
+            None => Ok(LogSource::File(LogReader::open(
+                path, tail_lines, tail_bytes, follow,
+            )?)),
+        }
+    }
+
+    /// Opens a log file to be read in place without following it, decompressing archives
+    /// instead since they cannot be read in place.
+    pub fn open_static<P: AsRef<Path>>(
+        path: P,
+        rules: EntryRules,
+        notifier: Notifier,
+    ) -> Result<LogSource, Error> {
+        let path = path.as_ref();
+        let mut file = File::open(path)
+            .map_err(|e| failure::format_err!("Failed opening {}: {}", path.display(), e))?;
+
+        match Compression::detect(&mut file)? {
+            Some(compression) => Ok(LogSource::Archive(PipeReader::archive(
+                path,
+                compression,
+                notifier,
+            )?)),
+            None => Ok(LogSource::Mapped(MappedReader::open(
+                path, rules, notifier,
+            )?)),
+        }
+    }
+
+    pub fn read_events(&mut self) -> Vec<LogEvent> {
+        match self {
+            LogSource::File(reader) => reader.read_events(),
+            LogSource::Mapped(reader) => reader.read_events(),
+            LogSource::Archive(reader) | LogSource::Pipe(reader) => reader.read_events(),
+            LogSource::Series(reader) => reader.read_events(),
+        }
+    }
+
+    /// Lines from before where a file started being read, see `LogReader::read_older_lines`.
+    /// Other sources are read from their start and have none.
+    pub fn read_older_lines(&mut self, bytes: u64) -> Result<Vec<String>, Error> {
+        match self {
+            LogSource::File(reader) => reader.read_older_lines(bytes),
+            _ => Ok(vec![]),
+        }
+    }
+
+    /// Tells whether the source has nothing more to give, which a followed file never does.
+    pub fn is_finished(&self) -> bool {
+        match self {
+            LogSource::File(reader) => reader.is_finished(),
+            LogSource::Archive(reader) | LogSource::Pipe(reader) => reader.open_stream_count == 0,
+            LogSource::Series(reader) => {
+                reader.remaining.is_empty() && reader.current.is_finished()
+            }
+            LogSource::Mapped(_) => false,
+        }
+    }
+
+    pub fn name(&self) -> String {
+        match self {
+            LogSource::File(reader) => reader.path().display().to_string(),
+            LogSource::Archive(reader) | LogSource::Pipe(reader) => reader.name.clone(),
+            LogSource::Series(reader) => reader.path.display().to_string(),
+            LogSource::Mapped(reader) => reader.path.display().to_string(),
+        }
+    }
+}
+
+/// Short names shown next to each line, the file name unless two sources share it.
+pub fn source_labels(sources: &[LogSource]) -> Vec<String> {
+    let names: Vec<String> = sources.iter().map(LogSource::name).collect();
+    let file_names: Vec<String> = names
+        .iter()
+        .map(|name| file_name(Path::new(name)))
+        .collect();
+
+    file_names
+        .iter()
+        .zip(&names)
+        .zip(sources)
+        .map(|((file_name, name), source)| match source {
+            LogSource::File(_)
+            | LogSource::Archive(_)
+            | LogSource::Series(_)
+            | LogSource::Mapped(_)
+                if file_names
+                    .iter()
+                    .filter(|other| *other == file_name)
+                    .count()
+                    == 1 =>
+            {
+                file_name.clone()
+            }
+            _ => name.clone(),
+        })
+        .collect()
+}
+
+/// Walks the file backwards in chunks until `lines` line breaks are found, not counting
+/// the one terminating the last line, and returns the offset where the first kept line starts.
+fn find_tail_start(file: &mut File, lines: usize) -> Result<u64, Error> {
+    let length = file.metadata()?.len();
+
+    if lines == 0 {
+        return Ok(length);
+    }
+
+    let mut buffer = vec![0; CHUNK_SIZE as usize];
+    let mut newline_count = 0;
+    let mut chunk_end = length;
+
+    while chunk_end > 0 {
+        let chunk_start = chunk_end.saturating_sub(CHUNK_SIZE);
+        let chunk = &mut buffer[..(chunk_end - chunk_start) as usize];
+
+        if !read_chunk(file, chunk_start, chunk)? {
+            return Ok(0);
+        }
+
+        for (index, byte) in chunk.iter().enumerate().rev() {
+            let offset = chunk_start + index as u64;
+
+            if *byte == b'\n' && offset != length - 1 {
+                newline_count += 1;
+
+                if newline_count == lines {
+                    return Ok(offset + 1);
+                }
+            }
+        }
+
+        chunk_end = chunk_start;
+    }
+
+    Ok(0)
+}
+
+/// Walks back from `offset` to the start of the line it is in.
+/// Reads `chunk` from `start`, telling whether the file still had all of it. A file that
+/// was truncated after its length was taken comes up short, which callers take as a reason
+/// to go back to its start instead of failing.
+fn read_chunk(file: &mut File, start: u64, chunk: &mut [u8]) -> Result<bool, Error> {
+    file.seek(SeekFrom::Start(start))?;
+
+    match file.read_exact(chunk) {
+        Ok(()) => Ok(true),
+        Err(error) if error.kind() == io::ErrorKind::UnexpectedEof => Ok(false),
+        Err(error) => Err(error.into()),
+    }
+}
+
+fn find_line_start(file: &mut File, offset: u64) -> Result<u64, Error> {
+    let mut buffer = vec![0; CHUNK_SIZE as usize];
+    let mut chunk_end = offset;
+
+    while chunk_end > 0 {
+        let chunk_start = chunk_end.saturating_sub(CHUNK_SIZE);
+        let chunk = &mut buffer[..(chunk_end - chunk_start) as usize];
+
+        if !read_chunk(file, chunk_start, chunk)? {
+            return Ok(0);
+        }
+
+        if let Some(index) = chunk.iter().rposition(|byte| *byte == b'\n') {
+            return Ok(chunk_start + index as u64 + 1);
+        }
+
+        chunk_end = chunk_start;
+    }
+
+    Ok(0)
+}
+
+/// Sends the lines of the stream until it closes, which is sent as `None`. An error, such
+/// as a corrupt compressed file, is sent before closing so it is not taken for the end.
+fn spawn_line_reader<R: Read + Send + 'static>(
+    stream: R,
+    tx: mpsc::Sender<Option<LogEvent>>,
+    notifier: Notifier,
+) {
+    thread::spawn(move || {
+        let mut reader = BufReader::new(stream);
+        let mut line = vec![];
+
+        loop {
+            line.clear();
+
+            let event = match reader.read_until(b'\n', &mut line) {
+                Ok(0) => break,
+                Ok(_) => LogEvent::Line(decode_line(&line)),
+                Err(error) => LogEvent::Failed(error.to_string()),
+            };
+            let is_failed = matches!(event, LogEvent::Failed(_));
+
+            if tx.send(Some(event)).is_err() {
+                return;
+            }
+
+            notifier.notify();
+
+            if is_failed {
+                break;
+            }
+        }
+
+        tx.send(None).ok();
+        notifier.notify();
+    });
+}
+
+/// Decodes the lines of an entry read in place, which keep their line breaks between them,
+/// leaving out the one ending it. Valid UTF-8 with plain line breaks is used as it is,
+/// without copying it.
+pub fn decode_entry(bytes: &[u8]) -> Cow<'_, str> {
+    let bytes = bytes.strip_suffix(b"\n").unwrap_or(bytes);
+    let bytes = bytes.strip_suffix(b"\r").unwrap_or(bytes);
+    let text = String::from_utf8_lossy(bytes);
+
+    if text.contains('\r') {
+        Cow::Owned(text.replace("\r\n", "\n"))
+    } else {
+        text
+    }
+}
+
+fn decode_line(bytes: &[u8]) -> String {
+    String::from_utf8_lossy(bytes)
+        .trim_end_matches(&['\n', '\r'][..])
+        .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::filter::MessageFilter;
+    use crate::parser::LineParser;
+    use crate::theme::Theme;
+    use std::io::Write;
+
+    const LINE: &[u8] = b"abcd\n";
+    // Puts a line break right at the start of the second chunk read from the end
+    const LINE_COUNT: usize = 30_000;
+
+    fn log_file() -> File {
+        let mut file = tempfile::tempfile().unwrap();
+
+        file.write_all(&LINE.repeat(LINE_COUNT)).unwrap();
+
+        file
+    }
+
+    #[test]
+    fn tail_start_is_found_across_chunks() {
+        let mut file = log_file();
+        let length = (LINE.len() * LINE_COUNT) as u64;
+
+        assert_eq!(
+            (length - CHUNK_SIZE) % LINE.len() as u64,
+            LINE.len() as u64 - 1
+        );
+
+        for &lines in &[1, 13_106, 13_107, 13_108, 26_214, 29_999, LINE_COUNT] {
+            assert_eq!(
+                find_tail_start(&mut file, lines).unwrap(),
+                length - (LINE.len() * lines) as u64,
+                "last {} lines",
+                lines
+            );
+        }
+
+        assert_eq!(find_tail_start(&mut file, 0).unwrap(), length);
+        assert_eq!(find_tail_start(&mut file, LINE_COUNT + 1).unwrap(), 0);
+    }
+
+    #[test]
+    fn line_start_is_found_across_chunks() {
+        let mut file = log_file();
+
+        for &offset in &[0, 1, 4, 5, 65_535, 65_536, 65_537, 131_071, 131_072] {
+            assert_eq!(
+                find_line_start(&mut file, offset).unwrap(),
+                offset - offset % LINE.len() as u64,
+                "offset {}",
+                offset
+            );
+        }
+    }
+
+    #[test]
+    fn line_starts_past_a_truncated_end_go_back_to_the_start() {
+        let mut file = log_file();
+        file.set_len(100).unwrap();
+
+        assert_eq!(find_line_start(&mut file, 200_000).unwrap(), 0);
+    }
+
+    #[test]
+    fn older_lines_of_a_truncated_file_are_not_read() {
+        let directory = tempfile::tempdir().unwrap();
+        let path = directory.path().join("app.log");
+        fs::write(&path, LINE.repeat(100)).unwrap();
+
+        let mut reader = LogReader::open(&path, Some(10), None, true).unwrap();
+        assert_eq!(reader.read_older_lines(10).unwrap(), vec!["abcd"; 2]);
+
+        File::create(&path).unwrap().set_len(50).unwrap();
+
+        assert!(reader.read_older_lines(1_000).unwrap().is_empty());
+        assert!(reader.read_older_lines(1_000).unwrap().is_empty());
+        assert!(matches!(
+            reader.read_events().as_slice(),
+            [LogEvent::Truncated, ..]
+        ));
+    }
+
+    #[test]
+    fn a_file_that_cannot_be_read_after_rotation_fails_once() {
+        let directory = tempfile::tempdir().unwrap();
+        let path = directory.path().join("app.log");
+        fs::write(&path, "first\n").unwrap();
+
+        let mut reader = LogReader::open(&path, None, None, true).unwrap();
+        assert!(matches!(reader.read_events().as_slice(), [LogEvent::Line(_)]));
+
+        // Opening a directory works, reading it does not
+        fs::remove_file(&path).unwrap();
+        fs::create_dir(&path).unwrap();
+
+        assert!(matches!(
+            reader.read_events().as_slice(),
+            [LogEvent::Rotated, LogEvent::Failed(_)]
+        ));
+        assert!(reader.read_events().is_empty());
+    }
+
+    #[test]
+    fn rotated_paths_are_oldest_first() {
+        let directory = tempfile::tempdir().unwrap();
+        let names = [
+            "server.log",
+            "server.log.1",
+            "server.log.2.gz",
+            "server.log.10",
+            "server.log.old",
+            "other.log.3",
+        ];
+
+        for name in &names {
+            File::create(directory.path().join(name)).unwrap();
+        }
+
+        let rotated: Vec<String> = rotated_paths(&directory.path().join("server.log"))
+            .unwrap()
+            .iter()
+            .map(|path| file_name(path))
+            .collect();
+
+        assert_eq!(
+            rotated,
+            vec!["server.log.10", "server.log.2.gz", "server.log.1"]
+        );
+    }
+
+    #[test]
+    fn mapped_entries_are_classified_again_once_the_tabs_change() {
+        let directory = tempfile::tempdir().unwrap();
+        let path = directory.path().join("app.log");
+        fs::write(&path, "first\r\n  continued\nsecond\nthird").unwrap();
+
+        let multiline = toml::from_str("continuation = \"indented\"").unwrap();
+        let rules = EntryRules::new(Some(&multiline)).unwrap();
+        let (tx, rx) = mpsc::channel();
+        let mut reader = MappedReader::open(&path, rules, Notifier { tx }).unwrap();
+        let classifier: SharedClassifier = Default::default();
+
+        reader.start_indexing("app.log", &classifier);
+        rx.recv().unwrap();
+
+        // The indexer thread is done, so its entries were classified without the filter
+        let filter = MessageFilter::new("second", "second", Style::default()).unwrap();
+        *classifier.write().unwrap() = Arc::new(Classifier::new(
+            &LineParser::default(),
+            &Theme::default(),
+            &[filter],
+        ));
+
+        let entries: Vec<(u64, Vec<usize>)> = reader
+            .read_events()
+            .into_iter()
+            .filter_map(|event| match event {
+                LogEvent::Mapped(entry) => Some((entry.end, entry.tabs)),
+                _ => None,
+            })
+            .collect();
+
+        assert_eq!(
+            entries,
+            vec![(19, vec![0]), (26, vec![0, 1]), (31, vec![0])]
+        );
+        assert_eq!(
+            decode_entry(b"first\r\n  continued\r\n"),
+            "first\n  continued"
+        );
+    }
+}
@@ -0,0 +1,100 @@
+use crate::store::{LineId, MessageStore};
+use crate::Notifier;
+use rayon::prelude::*;
+use regex::Regex;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{mpsc, Arc, RwLock};
+use std::thread;
+
+const SEARCH_CHUNK_SIZE: usize = 10_000;
+
+struct SearchJob {
+    generation: usize,
+    matcher: Regex,
+    candidates: Vec<LineId>,
+}
+
+/// Matches found in one chunk of a job, together with how many candidates the chunk had
+/// so the progress can be shown.
+pub struct SearchUpdate {
+    pub generation: usize,
+    pub matches: Vec<LineId>,
+    pub scanned_count: usize,
+}
+
+/// Runs searches over the store on a worker thread. Each search gets a new generation,
+/// which makes the worker drop whatever older search it is still scanning. The store is
+/// only locked for one chunk at a time so capturing lines is never held up for long.
+pub struct Searcher {
+    jobs: mpsc::Sender<SearchJob>,
+    updates: mpsc::Receiver<SearchUpdate>,
+    generation: Arc<AtomicUsize>,
+}
+
+impl Searcher {
+    pub fn new(store: Arc<RwLock<MessageStore>>, notifier: Notifier) -> Searcher {
+        let (jobs, job_rx) = mpsc::channel::<SearchJob>();
+        let (update_tx, updates) = mpsc::channel();
+        let generation = Arc::new(AtomicUsize::new(0));
+
+        let current_generation = Arc::clone(&generation);
+
+        thread::spawn(move || {
+            for job in job_rx {
+                for chunk in job.candidates.chunks(SEARCH_CHUNK_SIZE) {
+                    if current_generation.load(Ordering::SeqCst) != job.generation {
+                        break;
+                    }
+
+                    let matches = {
+                        let store = store.read().unwrap();
+
+                        chunk
+                            .par_iter()
+                            .filter(|id| store.is_match(**id, &job.matcher))
+                            .cloned()
+                            .collect()
+                    };
+
+                    let update = SearchUpdate {
+                        generation: job.generation,
+                        matches,
+                        scanned_count: chunk.len(),
+                    };
+
+                    if update_tx.send(update).is_err() {
+                        return;
+                    }
+
+                    notifier.notify_search_progress();
+                }
+            }
+        });
+
+        Searcher {
+            jobs,
+            updates,
+            generation,
+        }
+    }
+
+    /// Cancels the running search and returns the generation for the next one.
+    pub fn next_generation(&self) -> usize {
+        self.generation.fetch_add(1, Ordering::SeqCst) + 1
+    }
+
+    /// Queues candidates, in capture order, to be searched as part of `generation`.
+    pub fn search(&self, generation: usize, matcher: &Regex, candidates: Vec<LineId>) {
+        let job = SearchJob {
+            generation,
+            matcher: matcher.clone(),
+            candidates,
+        };
+
+        self.jobs.send(job).ok();
+    }
+
+    pub fn try_update(&self) -> Option<SearchUpdate> {
+        self.updates.try_recv().ok()
+    }
+}
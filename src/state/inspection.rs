@@ -1,3 +1,4 @@
+use crate::parser::{LineParser, LogRecord};
 use tui::widgets::Text;
 
 #[derive(Default)]
@@ -29,12 +30,21 @@ impl<'a> InspectionState<'a> {
         self.scroll_value = 0;
     }
 
-    pub fn inspect(&mut self, text: &Text) {
+    /// Shows the parsed parts of the line above its message, unless lines are not parsed.
+    pub fn inspect(&mut self, text: &Text, parser: &LineParser) {
         if let Text::Styled(cow, style) = text {
+            let (header, cow) = if parser.is_plain() {
+                (String::new(), cow.to_string())
+            } else {
+                let record = parser.parse(cow);
+
+                (record_header(&record), record.message)
+            };
+
             let json_opening_brace_index = match cow.find('{') {
                 Some(i) => i,
                 None => {
-                    self.text = Some(Text::styled(cow.to_string(), *style));
+                    self.text = Some(Text::styled(format!("{}{}", header, cow), *style));
 
                     return;
                 }
@@ -56,17 +66,18 @@ impl<'a> InspectionState<'a> {
             match serde_json::from_str::<serde_json::Value>(potential_json) {
                 Ok(json) => {
                     let text_to_display = format!(
-                        "{}\n{}\n{}",
+                        "{}{}\n{}\n{}",
+                        header,
                         &cow[..json_opening_brace_index].to_string(),
                         serde_json::to_string_pretty(&json).unwrap(),
                         &cow[json_closing_brace_index..].to_string()
                     );
 
-                    self.text = Some(Text::styled(text_to_display, *style));                    
+                    self.text = Some(Text::styled(text_to_display, *style));
                     self.is_json_format = true;
                 }
                 Err(_) => {
-                    self.text = Some(Text::styled(cow.to_string(), *style));
+                    self.text = Some(Text::styled(format!("{}{}", header, cow), *style));
                 }
             };
         }
@@ -83,3 +94,29 @@ impl<'a> InspectionState<'a> {
         }
     }
 }
+
+fn record_header(record: &LogRecord) -> String {
+    let mut header = String::new();
+
+    if let Some(timestamp) = &record.timestamp {
+        header.push_str(&format!("Time: {}\n", timestamp));
+    }
+
+    if let Some(level) = record.level {
+        header.push_str(&format!("Level: {}\n", level));
+    }
+
+    if let Some(logger) = &record.logger {
+        header.push_str(&format!("Logger: {}\n", logger));
+    }
+
+    for (name, value) in &record.fields {
+        header.push_str(&format!("{}: {}\n", name, value));
+    }
+
+    if !header.is_empty() {
+        header.push('\n');
+    }
+
+    header
+}
@@ -1,9 +1,13 @@
 mod inspection;
+mod prompt;
 mod search;
+mod sources;
 mod tabs;
 mod window;
 
 pub use self::inspection::InspectionState;
-pub use self::search::SearchState;
+pub use self::prompt::{PromptPurpose, PromptState};
+pub use self::search::{search_pattern, SavedSearch, SearchState};
+pub use self::sources::SourcesState;
 pub use self::tabs::TabsState;
-pub use self::window::WindowState;
+pub use self::window::{JumpTarget, WindowState};
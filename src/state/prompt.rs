@@ -0,0 +1,73 @@
+use unicode_width::UnicodeWidthStr;
+
+#[derive(Default)]
+pub enum PromptPurpose {
+    #[default]
+    RenameTab,
+    JumpTo,
+}
+
+/// A single line of input asked for in place of the tab bar.
+#[derive(Default)]
+pub struct PromptState {
+    pub is_initiated: bool,
+    pub purpose: PromptPurpose,
+    pub title: String,
+    pub input: String,
+    cursor_location: usize,
+}
+
+impl PromptState {
+    pub fn new() -> Self {
+        Self {
+            is_initiated: false,
+            purpose: PromptPurpose::RenameTab,
+            title: String::new(),
+            input: String::new(),
+            cursor_location: 0,
+        }
+    }
+
+    pub fn initiate(&mut self, purpose: PromptPurpose, title: &str, input: &str) {
+        self.is_initiated = true;
+        self.purpose = purpose;
+        self.title = title.to_string();
+        self.input = input.to_string();
+        self.cursor_location = self.input.len();
+    }
+
+    pub fn close(&mut self) {
+        self.is_initiated = false;
+        self.title = String::new();
+        self.input = String::new();
+        self.cursor_location = 0;
+    }
+
+    pub fn add_input(&mut self, character: char) {
+        self.input.insert(self.cursor_location, character);
+        self.cursor_location += character.len_utf8();
+    }
+
+    pub fn remove_input_backspace(&mut self) {
+        if let Some(character) = self.input[..self.cursor_location].chars().last() {
+            self.cursor_location -= character.len_utf8();
+            self.input.remove(self.cursor_location);
+        }
+    }
+
+    pub fn cursor_move_left(&mut self) {
+        if let Some(character) = self.input[..self.cursor_location].chars().last() {
+            self.cursor_location -= character.len_utf8();
+        }
+    }
+
+    pub fn cursor_move_right(&mut self) {
+        if let Some(character) = self.input[self.cursor_location..].chars().next() {
+            self.cursor_location += character.len_utf8();
+        }
+    }
+
+    pub fn get_cursor_location(&self) -> u16 {
+        self.input[0..self.cursor_location].width() as u16
+    }
+}
@@ -1,104 +1,389 @@
-use rayon::prelude::*;
-use tui::widgets::Text;
+use crate::history::SearchHistory;
+use crate::searcher::Searcher;
+use crate::store::LineId;
+use regex::Regex;
 use unicode_width::UnicodeWidthStr;
 
-#[derive(Default)]
-pub struct SearchState<'a> {
-    pub results: Vec<Text<'a>>,
+/// A named search from `[[saved_searches]]` in config.toml, recalled with Alt and its key.
+pub struct SavedSearch {
+    pub name: String,
+    pub query: String,
+    pub is_regex: bool,
+    pub is_case_sensitive: bool,
+    pub is_whole_word: bool,
+    pub key: Option<char>,
+}
+
+pub struct SearchState {
+    pub results: Vec<LineId>,
     pub is_initiated: bool,
+    pub is_editing: bool,
+    pub is_highlighting: bool,
     pub input: String,
     pub should_filter: bool,
+    pub matcher: Option<Regex>,
+    pub pending_jump: Option<bool>,
+    pub is_regex: bool,
+    pub is_case_sensitive: bool,
+    pub is_whole_word: bool,
+    pub error: Option<String>,
     cursor_location: usize,
+    searcher: Searcher,
+    generation: usize,
+    scanned_count: usize,
+    candidate_count: usize,
+    last_candidate: Option<LineId>,
+    refinable_input: Option<(String, bool)>,
+    history: SearchHistory,
+    history_position: Option<usize>,
+    draft_input: String,
+    saved_searches: Vec<SavedSearch>,
 }
 
-impl<'a> SearchState<'a> {
-    pub fn new() -> Self {
+impl SearchState {
+    pub fn new(
+        searcher: Searcher,
+        history: SearchHistory,
+        saved_searches: Vec<SavedSearch>,
+    ) -> Self {
         Self {
             results: vec![],
             is_initiated: false,
+            is_editing: false,
+            is_highlighting: false,
             input: String::new(),
             should_filter: false,
+            matcher: None,
+            pending_jump: None,
+            is_regex: false,
+            is_case_sensitive: false,
+            is_whole_word: false,
+            error: None,
             cursor_location: 0,
+            searcher,
+            generation: 0,
+            scanned_count: 0,
+            candidate_count: 0,
+            last_candidate: None,
+            refinable_input: None,
+            history,
+            history_position: None,
+            draft_input: String::new(),
+            saved_searches,
         }
     }
 
     pub fn initiate(&mut self) {
         self.is_initiated = true;
+        self.is_editing = true;
+    }
+
+    pub fn finish_editing(&mut self) {
+        self.is_editing = false;
+        self.history.add(&self.input);
+        self.history_position = None;
     }
 
     pub fn close(&mut self) {
+        self.history.add(&self.input);
+        self.history_position = None;
         self.is_initiated = false;
+        self.is_editing = false;
         self.input = String::new();
+        self.matcher = None;
+        self.pending_jump = None;
+        self.error = None;
         self.cursor_location = 0;
+        self.generation = self.searcher.next_generation();
+        self.results.clear();
+        self.refinable_input = None;
+    }
+
+    /// Searches again from scratch, as the lines searched are no longer the ones the
+    /// previous matches were found among, such as after switching tabs or sources.
+    pub fn invalidate(&mut self) {
+        self.should_filter = true;
+        self.refinable_input = None;
+    }
+
+    /// Highlighting keeps the whole tab in view instead of showing only the matching lines.
+    pub fn toggle_highlighting(&mut self) {
+        self.is_highlighting = !self.is_highlighting;
+    }
+
+    pub fn is_filtering(&self) -> bool {
+        self.is_initiated && !self.is_highlighting && !self.input.is_empty()
+    }
+
+    /// Asks for the selection to move to the next older match, or newer one, on next draw
+    /// when the lines shown are known.
+    pub fn jump(&mut self, is_older: bool) {
+        self.pending_jump = Some(is_older);
+    }
+
+    /// Describes where the selected line is among the matches, e.g. `match 3/57`.
+    pub fn match_counter(&self, selected: Option<LineId>) -> Option<String> {
+        if !self.is_initiated || self.input.is_empty() || self.error.is_some() {
+            return None;
+        }
+
+        let position = selected.and_then(|id| self.results.binary_search(&id).ok());
+
+        Some(match position {
+            Some(position) => format!("match {}/{}", position + 1, self.results.len()),
+            None => format!("{} matches", self.results.len()),
+        })
+    }
+
+    pub fn toggle_regex(&mut self) {
+        self.is_regex = !self.is_regex;
+        self.should_filter = true;
+    }
+
+    pub fn toggle_case_sensitive(&mut self) {
+        self.is_case_sensitive = !self.is_case_sensitive;
+        self.should_filter = true;
+    }
+
+    pub fn toggle_whole_word(&mut self) {
+        self.is_whole_word = !self.is_whole_word;
+        self.should_filter = true;
+    }
+
+    /// Describes the active modes for the search box title.
+    pub fn mode_title(&self) -> String {
+        let mut modes = vec![
+            if self.is_highlighting {
+                "highlight"
+            } else {
+                "filter"
+            },
+            if self.is_regex { "regex" } else { "literal" },
+        ];
+
+        if self.is_case_sensitive {
+            modes.push("case-sensitive");
+        }
+
+        if self.is_whole_word {
+            modes.push("whole word");
+        }
+
+        format!(
+            "Search: {} (^F highlight, ^R regex, ^S case, ^W word)",
+            modes.join(", ")
+        )
+    }
+
+    /// Builds the regex for the input in the current modes, literal input is escaped.
+    pub fn build_matcher(&self) -> Result<Regex, regex::Error> {
+        Regex::new(&search_pattern(
+            &self.input,
+            self.is_regex,
+            self.is_case_sensitive,
+            self.is_whole_word,
+        ))
+    }
+
+    /// Replaces the input with the previous entry of the search history, remembering what
+    /// was typed so far to come back to past the newest entry.
+    pub fn history_previous(&mut self) {
+        let position = match self.history_position {
+            _ if self.history.entries.is_empty() => return,
+            None => {
+                self.draft_input = self.input.clone();
+                self.history.entries.len() - 1
+            }
+            Some(position) => position.saturating_sub(1),
+        };
+
+        self.history_position = Some(position);
+        self.replace_input(self.history.entries[position].clone());
+    }
+
+    pub fn history_next(&mut self) {
+        let position = match self.history_position {
+            Some(position) => position + 1,
+            None => return,
+        };
+
+        if position < self.history.entries.len() {
+            self.history_position = Some(position);
+            self.replace_input(self.history.entries[position].clone());
+        } else {
+            self.history_position = None;
+            self.replace_input(self.draft_input.clone());
+        }
+    }
+
+    /// Runs the saved search bound to `key`, telling whether there is one.
+    pub fn recall(&mut self, key: char) -> bool {
+        let saved_search = match self
+            .saved_searches
+            .iter()
+            .find(|saved_search| saved_search.key == Some(key))
+        {
+            Some(saved_search) => saved_search,
+            None => return false,
+        };
+
+        self.is_regex = saved_search.is_regex;
+        self.is_case_sensitive = saved_search.is_case_sensitive;
+        self.is_whole_word = saved_search.is_whole_word;
+        self.replace_input(saved_search.query.clone());
+        self.is_initiated = true;
+        self.finish_editing();
+
+        true
     }
 
-    pub fn get_results(&mut self, lines: &[Text<'a>]) -> &[Text<'a>] {
-        //TODO: think about using LRU when user is deleting input and detach from UI thread/papralelise filtering
+    fn replace_input(&mut self, input: String) {
+        self.cursor_location = input.len();
+        self.input = input;
+        self.should_filter = true;
+    }
+
+    /// Collects what the worker found so far, then starts a new search when the input or
+    /// modes changed, or searches just the lines captured since the last call otherwise.
+    pub fn update_results(&mut self, lines: &[LineId]) {
+        while let Some(update) = self.searcher.try_update() {
+            if update.generation == self.generation {
+                self.results.extend(update.matches);
+                self.scanned_count += update.scanned_count;
+            }
+        }
+
         if self.should_filter {
             self.should_filter = false;
+            self.start_search(lines);
+        } else if let Some(matcher) = &self.matcher {
+            let new_lines = match self.last_candidate {
+                Some(last) => &lines[lines.partition_point(|id| *id <= last)..],
+                None => lines,
+            };
 
-            let search_input = &self.input.to_lowercase();
-            self.results = lines
-                .par_iter()
-                .filter(|line| match line {
-                    Text::Styled(cow, _) => cow.to_string().to_lowercase().contains(search_input),
-                    _ => false,
-                })
-                .cloned()
-                .collect();
+            if !new_lines.is_empty() {
+                self.candidate_count += new_lines.len();
+                self.last_candidate = new_lines.last().cloned();
+                self.searcher
+                    .search(self.generation, matcher, new_lines.to_vec());
+            }
         }
 
-        &self.results
+        // Lines past the retention limits are gone from the tab, so drop their matches too
+        if let Some(first) = lines.first() {
+            let dropped_count = self.results.partition_point(|id| id < first);
+            self.results.drain(..dropped_count);
+        }
     }
 
-    pub fn add_input(&mut self, character: char) {
-        let mut new_input = String::new();
+    /// Gives the search progress in percent while the worker is still scanning.
+    pub fn progress(&self) -> Option<usize> {
+        if self.matcher.is_none() || self.scanned_count >= self.candidate_count {
+            return None;
+        }
+
+        Some(self.scanned_count * 100 / self.candidate_count)
+    }
+
+    /// Extending a finished literal search only needs to look at its matches again, while
+    /// anything else is searched from scratch.
+    fn start_search(&mut self, lines: &[LineId]) {
+        let was_finished = self.matcher.is_some() && self.progress().is_none();
+        let previous_input = self.refinable_input.take();
+        let previous_last_candidate = self.last_candidate;
+
+        self.generation = self.searcher.next_generation();
+        self.scanned_count = 0;
+        self.candidate_count = 0;
+        self.last_candidate = lines.last().cloned();
+
+        let matcher = match self.build_matcher() {
+            Ok(matcher) => matcher,
+            Err(error) => {
+                self.error = Some(describe_regex_error(&error));
+                self.matcher = None;
+                self.results.clear();
+
+                return;
+            }
+        };
 
-        new_input.push_str(&self.input[0..self.cursor_location]);
-        new_input.push(character);
-        new_input.push_str(&self.input[self.cursor_location..]);
+        let is_refinement = match &previous_input {
+            Some((input, is_case_sensitive)) => {
+                was_finished
+                    && !self.is_regex
+                    && !self.is_whole_word
+                    && *is_case_sensitive == self.is_case_sensitive
+                    && self.input.len() > input.len()
+                    && self.input.starts_with(input.as_str())
+            }
+            None => false,
+        };
 
-        self.input = new_input;
-        self.cursor_move_right();
+        let candidates = if is_refinement {
+            let mut candidates = std::mem::take(&mut self.results);
+
+            candidates.extend_from_slice(match previous_last_candidate {
+                Some(last) => &lines[lines.partition_point(|id| *id <= last)..],
+                None => lines,
+            });
+
+            candidates
+        } else {
+            self.results.clear();
+            lines.to_vec()
+        };
+
+        if !self.is_regex && !self.is_whole_word {
+            self.refinable_input = Some((self.input.clone(), self.is_case_sensitive));
+        }
+
+        self.error = None;
+        self.candidate_count = candidates.len();
+        self.searcher.search(self.generation, &matcher, candidates);
+        self.matcher = Some(matcher);
+    }
+
+    pub fn add_input(&mut self, character: char) {
+        self.input.insert(self.cursor_location, character);
+        self.cursor_location += character.len_utf8();
         self.should_filter = true;
+        self.history_position = None;
     }
 
     pub fn remove_input_backspace(&mut self) {
-        if self.input.len() > 0 as usize {
-            let mut new_input = String::new();
-
-            new_input.push_str(&self.input[0..self.cursor_location - 1]);
-            new_input.push_str(&self.input[self.cursor_location..]);
-
-            self.input = new_input;
-            self.cursor_move_left();
+        if let Some(character) = self.input[..self.cursor_location].chars().last() {
+            self.cursor_location -= character.len_utf8();
+            self.input.remove(self.cursor_location);
             self.should_filter = true;
+            self.history_position = None;
         }
     }
 
     pub fn remove_input_delete(&mut self) {
-        if self.input.len() > 0 as usize {
-            let mut new_input = String::new();
-
-            new_input.push_str(&self.input[0..self.cursor_location]);
-            new_input.push_str(&self.input[self.cursor_location + 1..]);
-
-            self.input = new_input;
+        if self.cursor_location < self.input.len() {
+            self.input.remove(self.cursor_location);
             self.should_filter = true;
+            self.history_position = None;
         }
     }
 
+    /// Moves the cursor a character to the left, wrapping around to the last character.
     pub fn cursor_move_left(&mut self) {
-        if self.cursor_location == 0 && !self.input.is_empty() {
-            self.cursor_location = self.input.len() - 1;
-        } else if self.cursor_location > 0 {
-            self.cursor_location -= 1;
+        let before_cursor = match self.cursor_location {
+            0 => &self.input,
+            location => &self.input[..location],
+        };
+
+        if let Some(character) = before_cursor.chars().last() {
+            self.cursor_location = before_cursor.len() - character.len_utf8();
         }
     }
 
     pub fn cursor_move_right(&mut self) {
-        if self.input.len() > self.cursor_location {
-            self.cursor_location += 1;
+        if let Some(character) = self.input[self.cursor_location..].chars().next() {
+            self.cursor_location += character.len_utf8();
         }
     }
 
@@ -106,3 +391,281 @@ impl<'a> SearchState<'a> {
         self.input[0..self.cursor_location].width() as u16
     }
 }
+
+/// Gives the regex for a search, with literal input escaped.
+pub fn search_pattern(
+    input: &str,
+    is_regex: bool,
+    is_case_sensitive: bool,
+    is_whole_word: bool,
+) -> String {
+    let mut pattern = if is_regex {
+        input.to_string()
+    } else {
+        regex::escape(input)
+    };
+
+    if is_whole_word {
+        pattern = format!(r"\b(?:{})\b", pattern);
+    }
+
+    if !is_case_sensitive {
+        pattern = format!("(?i){}", pattern);
+    }
+
+    pattern
+}
+
+/// Syntax errors span several lines pointing at the pattern, only the last one with the
+/// actual reason fits into the search box.
+fn describe_regex_error(error: &regex::Error) -> String {
+    match error {
+        regex::Error::Syntax(message) => message
+            .lines()
+            .last()
+            .unwrap_or(message)
+            .trim_start_matches("error: ")
+            .to_string(),
+        error => error.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::store::{MessageStore, RetentionLimits, StoredLine};
+    use crate::Notifier;
+    use std::sync::{mpsc, Arc, RwLock};
+    use std::thread;
+    use std::time::{Duration, Instant};
+    use tui::style::Style;
+
+    fn search_state(texts: &[&str]) -> (SearchState, Vec<LineId>) {
+        let mut store = MessageStore::new(1, RetentionLimits::default()).unwrap();
+        let ids = texts
+            .iter()
+            .map(|text| {
+                let line = StoredLine {
+                    source: 0,
+                    text: text.to_string(),
+                    style: Style::default(),
+                    timestamp: None,
+                };
+
+                store.push(line, &[0]).unwrap()
+            })
+            .collect();
+        let (tx, _) = mpsc::channel();
+        let searcher = Searcher::new(Arc::new(RwLock::new(store)), Notifier { tx });
+
+        (
+            SearchState::new(searcher, SearchHistory::load_from(None), vec![]),
+            ids,
+        )
+    }
+
+    fn type_input(search: &mut SearchState, input: &str) {
+        input
+            .chars()
+            .for_each(|character| search.add_input(character));
+    }
+
+    fn finish_search(search: &mut SearchState, lines: &[LineId]) {
+        let started = Instant::now();
+        search.update_results(lines);
+
+        while search.progress().is_some() {
+            assert!(started.elapsed() < Duration::from_secs(10), "search hangs");
+            thread::sleep(Duration::from_millis(1));
+            search.update_results(lines);
+        }
+    }
+
+    #[test]
+    fn input_is_edited_by_characters() {
+        let (mut search, _) = search_state(&[]);
+
+        type_input(&mut search, "zé日");
+        assert_eq!(search.get_cursor_location(), 4);
+
+        search.cursor_move_left();
+        search.add_input('ü');
+        assert_eq!(search.input, "zéü日");
+        assert_eq!(search.get_cursor_location(), 3);
+
+        search.remove_input_delete();
+        search.remove_input_delete();
+        assert_eq!(search.input, "zéü");
+
+        search.remove_input_backspace();
+        search.cursor_move_right();
+        assert_eq!(search.input, "zé");
+        assert_eq!(search.get_cursor_location(), 2);
+
+        search.cursor_move_left();
+        search.cursor_move_left();
+        search.remove_input_backspace();
+        assert_eq!(search.input, "zé");
+
+        // Moving left from the start wraps around to the last character
+        search.cursor_move_left();
+        search.add_input('!');
+        assert_eq!(search.input, "z!é");
+    }
+
+    #[test]
+    fn matchers_follow_the_modes() {
+        let (mut search, _) = search_state(&[]);
+        type_input(&mut search, "a.b");
+
+        let matcher = search.build_matcher().unwrap();
+        assert!(matcher.is_match("x A.B y"));
+        assert!(!matcher.is_match("axb"));
+
+        search.toggle_regex();
+        search.toggle_case_sensitive();
+        assert!(search.build_matcher().unwrap().is_match("axb"));
+        assert!(!search.build_matcher().unwrap().is_match("AXB"));
+
+        search.toggle_whole_word();
+        assert!(search.build_matcher().unwrap().is_match("1 axb 2"));
+        assert!(!search.build_matcher().unwrap().is_match("1axb2"));
+        assert_eq!(
+            search.mode_title(),
+            "Search: filter, regex, case-sensitive, whole word (^F highlight, ^R regex, ^S case, ^W word)"
+        );
+    }
+
+    #[test]
+    fn invalid_regexes_are_reported_without_matches() {
+        let (mut search, ids) = search_state(&["a(b"]);
+        search.toggle_regex();
+        search.initiate();
+        type_input(&mut search, "a(");
+
+        search.update_results(&ids);
+
+        assert_eq!(search.error.as_deref(), Some("unclosed group"));
+        assert!(search.results.is_empty());
+        assert_eq!(search.match_counter(None), None);
+    }
+
+    #[test]
+    fn extended_literal_input_only_searches_the_previous_matches() {
+        let (mut search, ids) = search_state(&["error one", "warning", "error two", "errand"]);
+        search.initiate();
+
+        type_input(&mut search, "err");
+        finish_search(&mut search, &ids);
+        assert_eq!(search.results, vec![ids[0], ids[2], ids[3]]);
+
+        type_input(&mut search, "or");
+        finish_search(&mut search, &ids);
+        assert_eq!(search.candidate_count, 3);
+        assert_eq!(search.results, vec![ids[0], ids[2]]);
+
+        // Shorter input could match lines dropped before, so it searches everything again
+        search.remove_input_backspace();
+        finish_search(&mut search, &ids);
+        assert_eq!(search.candidate_count, 4);
+        assert_eq!(search.results, vec![ids[0], ids[2]]);
+
+        search.toggle_regex();
+        type_input(&mut search, "r");
+        finish_search(&mut search, &ids);
+        assert_eq!(search.candidate_count, 4);
+        assert_eq!(search.match_counter(Some(ids[2])).unwrap(), "match 2/2");
+    }
+
+    #[test]
+    fn matches_of_a_replaced_search_are_ignored() {
+        let (mut search, ids) = search_state(&["error one", "error two"]);
+        search.initiate();
+
+        type_input(&mut search, "err");
+        search.update_results(&ids);
+        search.add_input('x');
+        finish_search(&mut search, &ids);
+        assert!(search.results.is_empty());
+
+        search.remove_input_backspace();
+        search.update_results(&ids);
+        search.close();
+        thread::sleep(Duration::from_millis(20));
+        search.update_results(&ids);
+        assert!(search.results.is_empty());
+    }
+
+    #[test]
+    fn matches_of_dropped_lines_are_forgotten() {
+        let (mut search, ids) = search_state(&["error one", "ok", "error two"]);
+        search.initiate();
+
+        type_input(&mut search, "error");
+        finish_search(&mut search, &ids);
+        search.update_results(&ids[1..]);
+
+        assert_eq!(search.results, vec![ids[2]]);
+    }
+
+    #[test]
+    fn history_entries_replace_the_input_and_give_back_the_draft() {
+        let (mut search, _) = search_state(&[]);
+
+        for query in &["größe", "日本"] {
+            type_input(&mut search, query);
+            search.finish_editing();
+            search.close();
+        }
+
+        search.initiate();
+        type_input(&mut search, "dr");
+        search.history_previous();
+        assert_eq!(search.input, "日本");
+        search.history_previous();
+        search.history_previous();
+        assert_eq!(search.input, "größe");
+
+        // The cursor is at the end of the recalled input and moves by characters
+        search.cursor_move_left();
+        search.remove_input_backspace();
+        assert_eq!(search.input, "gröe");
+        assert_eq!(search.get_cursor_location(), 3);
+
+        // Edited input becomes the draft to come back to
+        search.history_previous();
+        assert_eq!(search.input, "日本");
+        search.history_next();
+        assert_eq!(search.input, "gröe");
+        assert_eq!(search.get_cursor_location(), 4);
+    }
+
+    #[test]
+    fn saved_searches_are_recalled_with_their_modes() {
+        let (search, _) = search_state(&[]);
+        let saved_search = SavedSearch {
+            name: "Errors".to_string(),
+            query: "ERR\\w+".to_string(),
+            is_regex: true,
+            is_case_sensitive: true,
+            is_whole_word: false,
+            key: Some('e'),
+        };
+        let mut search = SearchState::new(
+            search.searcher,
+            SearchHistory::load_from(None),
+            vec![saved_search],
+        );
+
+        assert!(!search.recall('x'));
+        assert!(search.recall('e'));
+        assert!(search.is_initiated && !search.is_editing);
+        assert!(search.is_regex && search.is_case_sensitive);
+        assert_eq!(search.input, "ERR\\w+");
+
+        search.cursor_move_left();
+        search.add_input('d');
+        assert_eq!(search.input, "ERR\\wd+");
+        assert_eq!(search.history.entries, vec!["ERR\\w+"]);
+    }
+}
@@ -0,0 +1,32 @@
+pub struct SourcesState {
+    pub titles: Vec<String>,
+    pub index: usize,
+}
+
+impl SourcesState {
+    pub fn new(labels: &[String]) -> Self {
+        let mut vector = labels.to_owned();
+        vector.splice(0..0, vec!["All sources".to_owned()]);
+
+        Self {
+            titles: vector,
+            index: 0,
+        }
+    }
+
+    pub fn next(&mut self) {
+        self.index = (self.index + 1) % self.titles.len();
+    }
+
+    pub fn has_multiple(&self) -> bool {
+        self.titles.len() > 2
+    }
+
+    pub fn is_selected(&self, source: usize) -> bool {
+        self.index == 0 || self.index == source + 1
+    }
+
+    pub fn label(&self, source: usize) -> &str {
+        &self.titles[source + 1]
+    }
+}
@@ -1,6 +1,38 @@
+use std::ops::Range;
+
+const ACTIVITY_SECONDS: usize = 8;
+const FLASH_SECONDS: i64 = 2;
+const SPARKLINE_BARS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+
 pub struct TabsState {
     pub titles: Vec<String>,
     pub index: usize,
+    pub saved_titles: Vec<Option<String>>,
+    pub status: Option<String>,
+    pub title_ranges: Vec<Range<u16>>,
+    pub title_row: Option<u16>,
+    activity: Vec<TabActivity>,
+    activity_second: Option<i64>,
+}
+
+/// How many lines a tab got in total, how many of them were there when it was last looked
+/// at and how many arrived in each of the last seconds.
+struct TabActivity {
+    captured_count: Option<usize>,
+    seen_count: usize,
+    rate: Vec<usize>,
+    last_capture_second: Option<i64>,
+}
+
+impl TabActivity {
+    fn new() -> Self {
+        Self {
+            captured_count: None,
+            seen_count: 0,
+            rate: vec![0; ACTIVITY_SECONDS],
+            last_capture_second: None,
+        }
+    }
 }
 
 impl TabsState {
@@ -9,8 +41,14 @@ impl TabsState {
         vector.splice(0..0, vec!["All".to_owned()]);
 
         Self {
+            saved_titles: vector.iter().cloned().map(Some).collect(),
+            activity: vector.iter().map(|_| TabActivity::new()).collect(),
             titles: vector,
             index: 0,
+            status: None,
+            title_ranges: vec![],
+            title_row: None,
+            activity_second: None,
         }
     }
 
@@ -25,4 +63,256 @@ impl TabsState {
             self.index = self.titles.len() - 1;
         }
     }
+
+    pub fn select(&mut self, index: usize) {
+        if index < self.titles.len() {
+            self.index = index;
+        }
+    }
+
+    /// Finds the tab whose title was drawn at the given screen position.
+    pub fn title_at(&self, x: u16, y: u16) -> Option<usize> {
+        if self.title_row != Some(y) {
+            return None;
+        }
+
+        self.title_ranges
+            .iter()
+            .position(|range| range.contains(&x))
+    }
+
+    /// Tells whether the tab is not in config.toml as it is, having been added or renamed.
+    pub fn is_unsaved(&self, index: usize) -> bool {
+        self.saved_titles[index].as_ref() != Some(&self.titles[index])
+    }
+
+    /// Titles as shown in the tab bar, with tabs not written to config.toml marked by `*`,
+    /// followed by the line count, the unread count and a sparkline of recent activity.
+    pub fn display_titles(&self) -> Vec<String> {
+        self.titles
+            .iter()
+            .zip(&self.activity)
+            .enumerate()
+            .map(|(index, (title, activity))| {
+                let mut text = title.clone();
+
+                if self.is_unsaved(index) {
+                    text.push('*');
+                }
+
+                if let Some(captured_count) = activity.captured_count {
+                    text.push_str(&format!(" {}", captured_count));
+
+                    if captured_count > activity.seen_count {
+                        text.push_str(&format!(" +{}", captured_count - activity.seen_count));
+                    }
+                }
+
+                if let Some(sparkline) = sparkline(&activity.rate) {
+                    text.push(' ');
+                    text.push_str(&sparkline);
+                }
+
+                text
+            })
+            .collect()
+    }
+
+    /// Takes the number of lines each tab got so far, counting the new ones towards the
+    /// current second. The current tab is always read.
+    pub fn update_activity(&mut self, captured_counts: &[usize], second: i64) {
+        let elapsed = match self.activity_second {
+            Some(activity_second) => (second - activity_second).max(0) as usize,
+            None => 0,
+        };
+
+        self.activity_second = Some(second);
+
+        for (index, (activity, &captured_count)) in
+            self.activity.iter_mut().zip(captured_counts).enumerate()
+        {
+            let shift = elapsed.min(ACTIVITY_SECONDS);
+            activity.rate.drain(..shift);
+            activity.rate.resize(ACTIVITY_SECONDS, 0);
+
+            match activity.captured_count {
+                Some(previous_count) if captured_count > previous_count => {
+                    activity.rate[ACTIVITY_SECONDS - 1] += captured_count - previous_count;
+                    activity.last_capture_second = Some(second);
+                }
+                Some(_) => {}
+                None => activity.seen_count = captured_count,
+            }
+
+            activity.captured_count = Some(captured_count);
+
+            if index == self.index {
+                activity.seen_count = captured_count;
+            }
+        }
+    }
+
+    pub fn has_unread(&self, index: usize) -> bool {
+        match self.activity[index].captured_count {
+            Some(captured_count) => captured_count > self.activity[index].seen_count,
+            None => false,
+        }
+    }
+
+    /// Tells whether the tab got lines in the last couple of seconds.
+    pub fn is_flashing(&self, index: usize) -> bool {
+        match (
+            self.activity[index].last_capture_second,
+            self.activity_second,
+        ) {
+            (Some(capture_second), Some(second)) => second - capture_second < FLASH_SECONDS,
+            _ => false,
+        }
+    }
+
+    pub fn add(&mut self, title: &str) {
+        self.titles.push(title.to_string());
+        self.saved_titles.push(None);
+        self.activity.push(TabActivity::new());
+        self.index = self.titles.len() - 1;
+    }
+
+    pub fn remove(&mut self, index: usize) {
+        self.titles.remove(index);
+        self.saved_titles.remove(index);
+        self.activity.remove(index);
+
+        if self.index >= self.titles.len() {
+            self.index = self.titles.len() - 1;
+        }
+    }
+
+    /// Swaps the current tab with its neighbour, never moving the "All" tab, and gives the
+    /// indexes that were swapped.
+    pub fn move_current(&mut self, is_forward: bool) -> Option<(usize, usize)> {
+        let other = match is_forward {
+            true if self.index + 1 < self.titles.len() => self.index + 1,
+            false if self.index > 1 => self.index - 1,
+            _ => return None,
+        };
+
+        if self.index == 0 {
+            return None;
+        }
+
+        self.titles.swap(self.index, other);
+        self.saved_titles.swap(self.index, other);
+        self.activity.swap(self.index, other);
+
+        let swapped = (self.index, other);
+        self.index = other;
+
+        Some(swapped)
+    }
+}
+
+/// Draws the per second counts as bars scaled to the busiest second, keeping the lowest bar
+/// for seconds without lines, or nothing when there were no lines at all.
+fn sparkline(rate: &[usize]) -> Option<String> {
+    let max = *rate.iter().max()?;
+
+    if max == 0 {
+        return None;
+    }
+
+    Some(
+        rate.iter()
+            .map(|&count| match count {
+                0 => SPARKLINE_BARS[0],
+                count => SPARKLINE_BARS[1 + count * (SPARKLINE_BARS.len() - 2) / max],
+            })
+            .collect(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tabs() -> TabsState {
+        TabsState::new(&["errors".to_string(), "warnings".to_string()])
+    }
+
+    #[test]
+    fn lines_arriving_in_other_tabs_are_unread_until_viewed() {
+        let mut tabs = tabs();
+
+        // Lines there before the first update count as read
+        tabs.update_activity(&[10, 4, 2], 100);
+        assert_eq!(
+            tabs.display_titles(),
+            vec!["All 10", "errors 4", "warnings 2"]
+        );
+        assert!(!tabs.has_unread(1) && !tabs.is_flashing(1));
+
+        tabs.update_activity(&[13, 7, 2], 100);
+        assert_eq!(tabs.display_titles()[0], "All 13 ▁▁▁▁▁▁▁█");
+        assert_eq!(tabs.display_titles()[1], "errors 7 +3 ▁▁▁▁▁▁▁█");
+        assert!(tabs.has_unread(1) && tabs.is_flashing(1));
+        assert!(!tabs.has_unread(0) && !tabs.has_unread(2));
+
+        tabs.select(1);
+        tabs.update_activity(&[13, 7, 2], 101);
+        assert!(!tabs.has_unread(1));
+        assert!(tabs.is_flashing(1));
+
+        tabs.update_activity(&[13, 7, 2], 102);
+        assert!(!tabs.is_flashing(1));
+    }
+
+    #[test]
+    fn the_sparkline_shifts_by_the_elapsed_seconds() {
+        let mut tabs = tabs();
+
+        tabs.update_activity(&[0, 0, 0], 100);
+        tabs.update_activity(&[4, 4, 0], 100);
+        tabs.update_activity(&[5, 5, 0], 102);
+        assert_eq!(tabs.display_titles()[1], "errors 5 +5 ▁▁▁▁▁█▁▃");
+
+        // Time going backwards does not shift anything
+        tabs.update_activity(&[5, 5, 0], 101);
+        assert_eq!(tabs.display_titles()[1], "errors 5 +5 ▁▁▁▁▁█▁▃");
+
+        tabs.update_activity(&[5, 5, 0], 101 + ACTIVITY_SECONDS as i64);
+        assert_eq!(tabs.display_titles()[1], "errors 5 +5");
+    }
+
+    #[test]
+    fn activity_moves_and_goes_with_its_tab() {
+        let mut tabs = tabs();
+        tabs.update_activity(&[0, 0, 0], 100);
+        tabs.update_activity(&[1, 0, 1], 100);
+
+        tabs.select(2);
+        assert_eq!(tabs.move_current(false), Some((2, 1)));
+        assert!(tabs.has_unread(1) && !tabs.has_unread(2));
+        assert_eq!(tabs.move_current(false), None);
+
+        tabs.remove(1);
+        assert!(!tabs.has_unread(1));
+
+        tabs.add("new");
+        assert_eq!(tabs.display_titles()[2], "new*");
+    }
+
+    #[test]
+    fn renamed_saved_tabs_are_unsaved_until_named_back() {
+        let mut tabs = tabs();
+
+        tabs.titles[1] = "failures".to_string();
+        assert!(tabs.is_unsaved(1) && !tabs.is_unsaved(2));
+        assert_eq!(tabs.display_titles()[1], "failures*");
+
+        tabs.titles[1] = "errors".to_string();
+        assert!(!tabs.is_unsaved(1));
+
+        tabs.add("new");
+        tabs.saved_titles[3] = Some("new".to_string());
+        assert_eq!(tabs.display_titles()[3], "new");
+    }
 }
@@ -1,29 +1,101 @@
-use tui::style::{Modifier, Style};
+use crate::parser::{parse_timestamp, LineParser};
+use crate::state::SourcesState;
+use crate::store::{LineId, MessageStore, StoredLine};
+use crate::widgets::wrapped_row_count;
+use crate::Config;
+use chrono::{NaiveDateTime, NaiveTime};
+use regex::Regex;
+use std::collections::HashSet;
+use std::ops::Range;
+use std::time::{Duration, Instant};
+use tui::layout::Rect;
+use tui::style::Style;
 use tui::widgets::Text;
+use unicode_width::UnicodeWidthStr;
 
 const BORDER_MARGIN: usize = 2;
+const COLUMN_SEPARATOR: &str = "  ";
+const HORIZONTAL_SCROLL_STEP: usize = 8;
+const DOUBLE_CLICK_MS: u64 = 400;
+
+/// Where the `:` prompt asks the selection to go.
+pub enum JumpTarget {
+    Line(usize),
+    DateTime(NaiveDateTime),
+    Time(NaiveTime),
+}
 
 #[derive(Default)]
 pub struct WindowState<'a> {
     pub lines: Vec<Text<'a>>,
+    pub highlights: Vec<Vec<Range<usize>>>,
     pub line_is_selected: bool,
     pub selected_line: Option<Text<'a>>,
     pub selected_line_index: usize,
     pub selected_line_index_relative: usize,
+    pub is_paused: bool,
+    pub new_line_count: usize,
+    pub pending_target: Option<JumpTarget>,
+    pub is_wrapping: bool,
+    pub horizontal_offset: usize,
+    pub wants_older_lines: bool,
+    max_line_width: usize,
+    area: Rect,
+    line_heights: Vec<usize>,
+    shown_ids: Vec<LineId>,
+    expanded_ids: HashSet<LineId>,
+    is_expanding_all: bool,
+    last_click: Option<(Instant, usize)>,
+    anchor: Option<LineId>,
     line_count: usize,
     displayed_line_amount: usize,
+    selected_style: Style,
+}
+
+impl JumpTarget {
+    /// Reads a line number, a full timestamp or a time of day such as `14:05`.
+    pub fn parse(input: &str) -> Option<JumpTarget> {
+        let input = input.trim();
+
+        if let Ok(number) = input.parse() {
+            return Some(JumpTarget::Line(number));
+        }
+
+        // Times of day are tried first, `parse_timestamp` would take them to be today's.
+        ["%H:%M:%S%.f", "%H:%M"]
+            .iter()
+            .find_map(|format| NaiveTime::parse_from_str(input, format).ok())
+            .map(JumpTarget::Time)
+            .or_else(|| parse_timestamp(input).map(JumpTarget::DateTime))
+    }
 }
 
 impl<'a> WindowState<'a> {
-    pub fn new() -> Self {
+    pub fn new(selected_style: Style) -> Self {
         Self {
             lines: vec![],
+            highlights: vec![],
             line_is_selected: false,
             selected_line: None,
             selected_line_index: 0,
             selected_line_index_relative: 0,
+            is_paused: false,
+            new_line_count: 0,
+            pending_target: None,
+            is_wrapping: false,
+            horizontal_offset: 0,
+            wants_older_lines: false,
+            max_line_width: 0,
+            area: Rect::default(),
+            line_heights: vec![],
+            shown_ids: vec![],
+            expanded_ids: HashSet::new(),
+            is_expanding_all: false,
+            last_click: None,
+            anchor: None,
             line_count: 0,
             displayed_line_amount: 0,
+            selected_style,
         }
     }
 
@@ -39,6 +111,8 @@ impl<'a> WindowState<'a> {
 
     pub fn previous(&mut self) {
         if self.line_count <= self.selected_line_index + 1 {
+            self.wants_older_lines = true;
+
             return;
         }
 
@@ -50,23 +124,380 @@ impl<'a> WindowState<'a> {
         };
     }
 
-    pub fn display_lines(&mut self, lines: &[Text<'a>], window_height: usize) {
-        self.calculate_displayed_line_amount(window_height);
+    /// Pausing keeps the shown lines in place while new ones arrive. A selected line does
+    /// the same, so following again goes back to the newest line and drops the selection.
+    pub fn toggle_pause(&mut self) {
+        if self.is_paused || self.line_is_selected {
+            self.follow();
+        } else {
+            self.is_paused = true;
+        }
+    }
+
+    /// Gives the lines up to the newest one there was when the view got paused or a line
+    /// got selected, so the view stays on the same lines, and counts the lines after it.
+    pub fn anchor_lines<'l>(&mut self, lines: &'l [LineId]) -> &'l [LineId] {
+        if !self.is_paused && !self.line_is_selected {
+            self.anchor = None;
+            self.new_line_count = 0;
+
+            return lines;
+        }
+
+        let anchor = match (self.anchor, lines.last()) {
+            (Some(anchor), _) => anchor,
+            (None, Some(last)) => *self.anchor.insert(*last),
+            (None, None) => return lines,
+        };
+
+        let anchored_count = lines.partition_point(|id| *id <= anchor);
+        self.new_line_count = lines.len() - anchored_count;
+
+        &lines[..anchored_count]
+    }
+
+    pub fn display_lines(
+        &mut self,
+        lines: &[LineId],
+        store: &MessageStore,
+        area: Rect,
+        sources: &SourcesState,
+        config: &Config,
+        highlight: Option<&Regex>,
+    ) {
+        self.area = area;
+        self.calculate_displayed_line_amount(area.height as usize);
         self.calculate_relative_selected_line_index();
         self.line_count = lines.len();
 
-        let skipped_line_amount = self.selected_line_index - self.selected_line_index_relative;
+        let row_width = (area.width as usize).saturating_sub(BORDER_MARGIN);
+
+        // Wrapped lines and expanded entries take several rows, so fewer of them fit and the
+        // viewport moves down until the selected one is among those that do
+        let messages = loop {
+            let skipped_line_amount = self.selected_line_index - self.selected_line_index_relative;
+
+            let (ids, messages): (Vec<LineId>, Vec<StoredLine>) = lines
+                .iter()
+                .rev()
+                .skip(skipped_line_amount)
+                .take(self.displayed_line_amount)
+                .filter_map(|id| store.get(*id).map(|line| (*id, line.into_owned())))
+                .unzip();
+
+            let (shown_lines, highlights): (Vec<Text>, Vec<Vec<Range<usize>>>) = messages
+                .iter()
+                .zip(&ids)
+                .zip(format_columns(
+                    &messages,
+                    &config.parser,
+                    &config.json_columns,
+                ))
+                .map(|((message, id), (text, stored_parts))| {
+                    let is_expanded = self.is_expanded(*id);
+                    let shown_length = match text.find('\n') {
+                        Some(end) if !is_expanded => end,
+                        _ => text.len(),
+                    };
+                    let prefix = source_prefix(message, sources);
+                    let highlights = highlight
+                        .map(|highlight| find_highlights(highlight, &text, &stored_parts))
+                        .unwrap_or_default()
+                        .into_iter()
+                        .filter(|found| found.end <= shown_length)
+                        .map(|found| found.start + prefix.len()..found.end + prefix.len())
+                        .collect();
+                    let text = if is_expanded {
+                        text
+                    } else {
+                        collapse_entry(text)
+                    };
+
+                    (
+                        Text::styled(format!("{}{}", prefix, text), message.style),
+                        highlights,
+                    )
+                })
+                .unzip();
+            self.lines = shown_lines;
+            self.highlights = highlights;
+            self.shown_ids = ids;
+
+            self.line_heights = self
+                .lines
+                .iter()
+                .map(|line| match line {
+                    Text::Raw(text) | Text::Styled(text, _) if self.is_wrapping => {
+                        wrapped_row_count(text, row_width)
+                    }
+                    Text::Raw(text) | Text::Styled(text, _) => text.split('\n').count(),
+                })
+                .collect();
 
-        self.lines = lines
+            // Nothing is left to select once the lines before the selection are gone
+            if self.lines.is_empty() {
+                self.reset();
+
+                break messages;
+            }
+
+            let mut used_rows = 0;
+            let fitting_count = self
+                .line_heights
+                .iter()
+                .take_while(|height| {
+                    used_rows += *height;
+                    used_rows <= self.displayed_line_amount
+                })
+                .count()
+                .clamp(1, self.lines.len());
+
+            if !self.line_is_selected || self.selected_line_index_relative < fitting_count {
+                self.lines.truncate(fitting_count);
+                self.line_heights.truncate(fitting_count);
+                self.highlights.truncate(fitting_count);
+
+                break messages;
+            }
+
+            self.selected_line_index_relative = fitting_count - 1;
+        };
+
+        self.max_line_width = self
+            .lines
             .iter()
-            .rev()
-            .skip(skipped_line_amount)
-            .take(self.displayed_line_amount)
-            .cloned()
-            .collect();
+            .flat_map(|line| match line {
+                Text::Raw(text) | Text::Styled(text, _) => text.split('\n'),
+            })
+            .map(|line| line.width())
+            .max()
+            .unwrap_or(0);
+        self.horizontal_offset = self
+            .horizontal_offset
+            .min(self.max_line_width.saturating_sub(row_width));
 
         if self.line_is_selected {
-            self.apply_selected_style();
+            if let Some(selected) = messages.get(self.selected_line_index_relative) {
+                self.apply_selected_style(Text::styled(selected.text.clone(), selected.style));
+            }
+        }
+    }
+
+    /// Expands the selected entry to all its lines, or collapses it back to the first.
+    pub fn toggle_entry(&mut self) {
+        if !self.line_is_selected {
+            return;
+        }
+
+        if let Some(id) = self.shown_ids.get(self.selected_line_index_relative) {
+            if !self.expanded_ids.remove(id) {
+                self.expanded_ids.insert(*id);
+            }
+        }
+    }
+
+    pub fn toggle_all_entries(&mut self) {
+        self.is_expanding_all = !self.is_expanding_all;
+        self.expanded_ids.clear();
+    }
+
+    /// Entries toggled one by one are shown the other way than the rest.
+    fn is_expanded(&self, id: LineId) -> bool {
+        self.is_expanding_all != self.expanded_ids.contains(&id)
+    }
+
+    /// Selects the line drawn at the given screen position, telling whether the same line
+    /// was clicked just before so the click counts as a double click.
+    pub fn click(&mut self, x: u16, y: u16) -> bool {
+        let inner_bottom = self.area.bottom().saturating_sub(1);
+
+        if x <= self.area.left()
+            || x + 1 >= self.area.right()
+            || y <= self.area.top()
+            || y >= inner_bottom
+        {
+            return false;
+        }
+
+        let mut row_bottom = inner_bottom;
+        let relative_index = self.line_heights.iter().position(|height| {
+            row_bottom = row_bottom.saturating_sub(*height as u16);
+            y >= row_bottom
+        });
+
+        let relative_index = match relative_index {
+            Some(relative_index) => relative_index,
+            None => return false,
+        };
+
+        let index = self.selected_line_index - self.selected_line_index_relative + relative_index;
+        let is_double_click = match self.last_click {
+            Some((time, clicked_index)) => {
+                clicked_index == index && time.elapsed() < Duration::from_millis(DOUBLE_CLICK_MS)
+            }
+            None => false,
+        };
+
+        self.select(index);
+        self.last_click = Some((Instant::now(), index));
+
+        is_double_click
+    }
+
+    /// Wrapping shows long lines across several rows instead of cutting them off, which
+    /// also makes scrolling sideways pointless.
+    pub fn toggle_wrap(&mut self) {
+        self.is_wrapping = !self.is_wrapping;
+        self.horizontal_offset = 0;
+    }
+
+    pub fn scroll_left(&mut self) {
+        self.horizontal_offset = self
+            .horizontal_offset
+            .saturating_sub(HORIZONTAL_SCROLL_STEP);
+    }
+
+    pub fn scroll_right(&mut self) {
+        if !self.is_wrapping && self.horizontal_offset < self.max_line_width {
+            self.horizontal_offset += HORIZONTAL_SCROLL_STEP;
+        }
+    }
+
+    /// Selects the line at `index` counted from the newest, keeping the viewport where it is
+    /// if the line is already shown and centering on the line otherwise.
+    pub fn select(&mut self, index: usize) {
+        let first_shown_index = self.selected_line_index - self.selected_line_index_relative;
+
+        self.selected_line_index_relative = if index >= first_shown_index
+            && index < first_shown_index + self.displayed_line_amount
+        {
+            index - first_shown_index
+        } else {
+            index.min(self.displayed_line_amount / 2)
+        };
+
+        self.place_selection(index);
+    }
+
+    /// Moves the selection a page towards older lines, keeping it on the same row.
+    pub fn page_up(&mut self) {
+        if self.line_count == 0 {
+            return;
+        }
+
+        let index = if self.line_is_selected {
+            self.selected_line_index + self.displayed_line_amount
+        } else {
+            self.selected_line_index_relative = self.displayed_line_amount.saturating_sub(1);
+            (self.displayed_line_amount * 2).saturating_sub(1)
+        };
+
+        self.wants_older_lines = index >= self.line_count - 1;
+        self.place_selection(index.min(self.line_count - 1));
+    }
+
+    pub fn page_down(&mut self) {
+        if !self.line_is_selected {
+            return;
+        }
+
+        self.place_selection(
+            self.selected_line_index
+                .saturating_sub(self.displayed_line_amount),
+        );
+    }
+
+    /// Selects the oldest line.
+    pub fn first(&mut self) {
+        self.wants_older_lines = true;
+
+        if self.line_count > 0 {
+            self.place_selection(self.line_count - 1);
+        }
+    }
+
+    /// Goes back to showing the newest lines as they arrive.
+    pub fn follow(&mut self) {
+        self.is_paused = false;
+        self.reset();
+    }
+
+    /// Selects the line closest to the target in `lines`, which are in capture order. Lines
+    /// are numbered from 1 for the oldest, timestamps go to the first line at or after them.
+    pub fn jump_to(
+        &mut self,
+        target: &JumpTarget,
+        lines: &[LineId],
+        store: &MessageStore,
+        parser: &LineParser,
+    ) -> Result<(), String> {
+        if lines.is_empty() {
+            return Err("No lines to jump to".to_string());
+        }
+
+        let position = match target {
+            JumpTarget::Line(number) => number.saturating_sub(1),
+            _ if parser.is_plain() => {
+                return Err("Jumping to a time needs a [parser] that reads timestamps".to_string())
+            }
+            JumpTarget::DateTime(_) | JumpTarget::Time(_) => {
+                first_logged_at(lines, store, |timestamp| match target {
+                    JumpTarget::DateTime(target) => timestamp < *target,
+                    JumpTarget::Time(target) => timestamp.time() < *target,
+                    JumpTarget::Line(_) => false,
+                })
+            }
+        };
+
+        self.select(lines.len() - 1 - position.min(lines.len() - 1));
+
+        Ok(())
+    }
+
+    /// Selects the line at `index` on the current row, as far as the lines go, so the
+    /// viewport never shows empty rows above the oldest line.
+    fn place_selection(&mut self, index: usize) {
+        let lowest_row =
+            index.saturating_sub(self.line_count.saturating_sub(self.displayed_line_amount));
+
+        self.selected_line_index_relative = self
+            .selected_line_index_relative
+            .min(index)
+            .min(self.displayed_line_amount.saturating_sub(1))
+            .max(lowest_row);
+        self.selected_line_index = index;
+        self.line_is_selected = true;
+    }
+
+    pub fn selected_id(&self, lines: &[LineId]) -> Option<LineId> {
+        if !self.line_is_selected || self.selected_line_index >= lines.len() {
+            return None;
+        }
+
+        Some(lines[lines.len() - 1 - self.selected_line_index])
+    }
+
+    /// Moves the selection to the closest match older or newer than the selected line,
+    /// wrapping around at either end. Both `lines` and `matches` are in capture order.
+    pub fn jump_to_match(&mut self, lines: &[LineId], matches: &[LineId], is_older: bool) {
+        if matches.is_empty() {
+            return;
+        }
+
+        let target = match self.selected_id(lines) {
+            None => matches[matches.len() - 1],
+            Some(selected) if is_older => match matches.iter().rev().find(|id| **id < selected) {
+                Some(id) => *id,
+                None => matches[matches.len() - 1],
+            },
+            Some(selected) => match matches.iter().find(|id| **id > selected) {
+                Some(id) => *id,
+                None => matches[0],
+            },
+        };
+
+        if let Ok(position) = lines.binary_search(&target) {
+            self.select(lines.len() - 1 - position);
         }
     }
 
@@ -76,27 +507,454 @@ impl<'a> WindowState<'a> {
         self.selected_line_index_relative = 0;
     }
 
-    fn apply_selected_style(&mut self) {
-        if let Text::Styled(cow, style) = &self.lines[self.selected_line_index_relative] {
+    fn apply_selected_style(&mut self, selected_text: Text<'a>) {
+        if let Text::Styled(cow, _) = &self.lines[self.selected_line_index_relative] {
             let text_value = cow.to_string();
-            let style_value = *style;
 
-            self.lines[self.selected_line_index_relative] = Text::styled(
-                text_value.clone(),
-                Style::default().modifier(Modifier::REVERSED),
-            );
+            self.lines[self.selected_line_index_relative] =
+                Text::styled(text_value, self.selected_style);
 
-            self.selected_line = Some(Text::styled(text_value, style_value));
+            self.selected_line = Some(selected_text);
         }
     }
 
     fn calculate_displayed_line_amount(&mut self, window_height: usize) {
-        self.displayed_line_amount = window_height - BORDER_MARGIN;
+        self.displayed_line_amount = window_height.saturating_sub(BORDER_MARGIN);
     }
 
     fn calculate_relative_selected_line_index(&mut self) {
-        if self.selected_line_index_relative >= self.displayed_line_amount - 1 {
-            self.selected_line_index_relative = self.displayed_line_amount - 1;
+        let last_row = self.displayed_line_amount.saturating_sub(1);
+
+        if self.selected_line_index_relative >= last_row {
+            self.selected_line_index_relative = last_row;
+        }
+    }
+}
+
+/// Finds the first line with a timestamp that `is_before` is false for, or the end of
+/// `lines`. Lines without one, such as markers, are skipped rather than searched, since
+/// they would break the order the binary search relies on.
+fn first_logged_at<F>(lines: &[LineId], store: &MessageStore, is_before: F) -> usize
+where
+    F: Fn(NaiveDateTime) -> bool,
+{
+    let timestamp = |index: usize| store.get(lines[index]).and_then(|line| line.timestamp);
+    let (mut low, mut high) = (0, lines.len());
+
+    while low < high {
+        let middle = low + (high - low) / 2;
+
+        match (middle..high).find_map(|index| Some((index, timestamp(index)?))) {
+            Some((index, timestamp)) if is_before(timestamp) => low = index + 1,
+            _ => high = middle,
+        }
+    }
+
+    (low..lines.len())
+        .find(|index| timestamp(*index).is_some())
+        .unwrap_or(lines.len())
+}
+
+/// Shows an entry of several lines as its first line and how many more there are.
+fn collapse_entry(text: String) -> String {
+    match text.find('\n') {
+        Some(end) => format!(
+            "{} [+{} lines]",
+            &text[..end],
+            text[end + 1..].split('\n').count()
+        ),
+        None => text,
+    }
+}
+
+fn source_prefix(line: &StoredLine, sources: &SourcesState) -> String {
+    if sources.has_multiple() {
+        format!("[{}] ", sources.label(line.source))
+    } else {
+        String::new()
+    }
+}
+
+/// Finds the matches in the parts of a shown text that hold stored text, so the search
+/// highlights what it matched rather than labels, padding or separators.
+fn find_highlights(
+    highlight: &Regex,
+    text: &str,
+    stored_parts: &[Range<usize>],
+) -> Vec<Range<usize>> {
+    stored_parts
+        .iter()
+        .flat_map(|part| {
+            highlight
+                .find_iter(&text[part.clone()])
+                .filter(|found| !found.as_str().is_empty())
+                .map(move |found| part.start + found.start()..part.start + found.end())
+        })
+        .collect()
+}
+
+/// Renders the given fields of parsed lines as columns as wide as their widest value on
+/// screen, collapsing the other fields into a count. Lines the parser does not recognise
+/// are shown as they are. Each text comes with the ranges of it that hold stored text,
+/// the whole line or the cells.
+fn format_columns(
+    messages: &[StoredLine],
+    parser: &LineParser,
+    columns: &[String],
+) -> Vec<(String, Vec<Range<usize>>)> {
+    let as_stored = |message: &StoredLine| {
+        let range = 0..message.text.len();
+
+        (message.text.clone(), vec![range])
+    };
+
+    if columns.is_empty() {
+        return messages.iter().map(as_stored).collect();
+    }
+
+    let rows: Vec<Option<(Vec<String>, usize)>> = messages
+        .iter()
+        .map(|message| {
+            let record = parser.parse_structured(&message.text)?;
+            let cells = columns
+                .iter()
+                .map(|column| record.field(column).unwrap_or("").to_string())
+                .collect();
+            let collapsed_count = record
+                .fields
+                .iter()
+                .filter(|(name, _)| !columns.contains(name))
+                .count();
+
+            Some((cells, collapsed_count))
+        })
+        .collect();
+
+    let mut widths = vec![0; columns.len()];
+
+    for (cells, _) in rows.iter().flatten() {
+        for (width, cell) in widths.iter_mut().zip(cells) {
+            *width = (*width).max(cell.width());
+        }
+    }
+
+    rows.into_iter()
+        .zip(messages)
+        .map(|(row, message)| match row {
+            Some((cells, collapsed_count)) => {
+                let mut text = String::new();
+                let mut cell_ranges = vec![];
+
+                for (index, (cell, width)) in cells.iter().zip(&widths).enumerate() {
+                    if index > 0 {
+                        text.push_str(COLUMN_SEPARATOR);
+                    }
+
+                    cell_ranges.push(text.len()..text.len() + cell.len());
+                    text.push_str(cell);
+                    text.push_str(&" ".repeat(width - cell.width()));
+                }
+
+                if collapsed_count > 0 {
+                    text.push_str(&format!("{}{{+{}}}", COLUMN_SEPARATOR, collapsed_count));
+                }
+
+                let text = text.trim_end().to_string();
+
+                // Trimming also takes the trailing whitespace of the last cell
+                for range in &mut cell_ranges {
+                    range.end = range.end.min(text.len());
+                    range.start = range.start.min(range.end);
+                }
+
+                (text, cell_ranges)
+            }
+            None => as_stored(message),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::store::RetentionLimits;
+
+    fn config() -> Config {
+        Config {
+            path: None,
+            log_paths: vec![],
+            commands: vec![],
+            message_filters: vec![],
+            parser: LineParser::default(),
+            entry_rules: Default::default(),
+            json_columns: vec![],
+            saved_searches: vec![],
+            follow: true,
+            rotated: false,
+            is_static: false,
+            tail_lines: None,
+            tail_bytes: None,
+            tick_rate: None,
+            theme: Default::default(),
+            retention: RetentionLimits::default(),
+            classifier: Default::default(),
+        }
+    }
+
+    fn store(timestamps: &[Option<&str>]) -> (MessageStore, Vec<LineId>) {
+        let mut store = MessageStore::new(1, RetentionLimits::default()).unwrap();
+        let ids = timestamps
+            .iter()
+            .enumerate()
+            .map(|(index, timestamp)| {
+                let line = StoredLine {
+                    source: 0,
+                    text: index.to_string(),
+                    style: Style::default(),
+                    timestamp: timestamp.and_then(parse_timestamp),
+                };
+
+                store.push(line, &[0]).unwrap()
+            })
+            .collect();
+
+        (store, ids)
+    }
+
+    fn shown_window(line_count: usize, height: u16) -> (WindowState<'static>, Vec<LineId>) {
+        let (store, ids) = store(&vec![None; line_count]);
+        let mut window = WindowState::new(Style::default());
+
+        window.display_lines(
+            &ids,
+            &store,
+            Rect::new(0, 0, 40, height),
+            &SourcesState::new(&["app.log".to_string()]),
+            &config(),
+            None,
+        );
+
+        (window, ids)
+    }
+
+    #[test]
+    fn page_up_keeps_the_row_and_moves_a_page() {
+        let (mut window, _) = shown_window(100, 12);
+
+        window.page_up();
+        assert_eq!(window.selected_line_index, 19);
+        assert_eq!(window.selected_line_index_relative, 9);
+
+        window.page_up();
+        assert_eq!(window.selected_line_index, 29);
+
+        window.page_down();
+        assert_eq!(window.selected_line_index, 19);
+        assert!(!window.wants_older_lines);
+    }
+
+    #[test]
+    fn page_up_stops_at_the_oldest_line_and_asks_for_older_ones() {
+        let (mut window, _) = shown_window(15, 12);
+
+        window.page_up();
+
+        assert_eq!(window.selected_line_index, 14);
+        assert!(window.wants_older_lines);
+    }
+
+    #[test]
+    fn paging_without_rows_does_not_underflow() {
+        for height in 0..3 {
+            let (mut window, _) = shown_window(5, height);
+
+            window.page_up();
+            window.page_down();
+            window.first();
         }
     }
+
+    #[test]
+    fn jumping_to_a_time_skips_lines_without_one() {
+        let (store, ids) = store(&[
+            Some("2024-01-01 10:00:00"),
+            None,
+            Some("2024-01-01 10:05:00"),
+            None,
+            None,
+            Some("2024-01-01 10:10:00"),
+            Some("2024-01-01 10:15:00"),
+            None,
+        ]);
+        let parser = LineParser::preset("logback").unwrap();
+        let mut window = WindowState::new(Style::default());
+
+        let mut jump = |input: &str| {
+            let target = JumpTarget::parse(input).unwrap();
+            window.jump_to(&target, &ids, &store, &parser).unwrap();
+
+            store
+                .get(window.selected_id(&ids).unwrap())
+                .unwrap()
+                .text
+                .clone()
+        };
+
+        assert_eq!(jump("2024-01-01 10:05:00"), "2");
+        assert_eq!(jump("10:06"), "5");
+        assert_eq!(jump("09:00"), "0");
+        assert_eq!(jump("10:16"), "7");
+        assert_eq!(jump("3"), "2");
+    }
+
+    #[test]
+    fn jumping_to_a_time_needs_a_parser() {
+        let (store, ids) = store(&[None]);
+        let mut window = WindowState::new(Style::default());
+        let target = JumpTarget::parse("10:00").unwrap();
+
+        assert!(window
+            .jump_to(&target, &ids, &store, &LineParser::default())
+            .is_err());
+    }
+
+    #[test]
+    fn pausing_keeps_the_lines_and_counts_the_new_ones() {
+        let mut window = WindowState::new(Style::default());
+        let lines = [1, 2, 3, 4, 5];
+
+        assert_eq!(window.anchor_lines(&lines[..3]), &[1, 2, 3]);
+
+        window.toggle_pause();
+
+        assert_eq!(window.anchor_lines(&lines[..3]), &[1, 2, 3]);
+        assert_eq!(window.anchor_lines(&lines), &[1, 2, 3]);
+        assert_eq!(window.new_line_count, 2);
+
+        // Lines dropped from the front do not move the anchor
+        assert_eq!(window.anchor_lines(&lines[2..]), &[3]);
+        assert_eq!(window.new_line_count, 2);
+
+        window.toggle_pause();
+
+        assert_eq!(window.anchor_lines(&lines), &lines);
+        assert_eq!(window.new_line_count, 0);
+    }
+
+    #[test]
+    fn selecting_a_line_anchors_like_pausing() {
+        let (mut window, ids) = shown_window(20, 12);
+
+        window.previous();
+        assert_eq!(window.anchor_lines(&ids).len(), 20);
+
+        let mut more_ids = ids.clone();
+        more_ids.push(ids[19] + 1);
+
+        assert_eq!(window.anchor_lines(&more_ids), &ids[..]);
+        assert_eq!(window.new_line_count, 1);
+
+        window.follow();
+        assert_eq!(window.anchor_lines(&more_ids).len(), 21);
+    }
+
+    fn shown_highlights(
+        texts: &[(usize, &str)],
+        labels: &[&str],
+        config: &Config,
+        highlight: &str,
+    ) -> Vec<(String, Vec<String>)> {
+        let mut store = MessageStore::new(1, RetentionLimits::default()).unwrap();
+        let ids: Vec<LineId> = texts
+            .iter()
+            .map(|(source, text)| {
+                let line = StoredLine {
+                    source: *source,
+                    text: text.to_string(),
+                    style: Style::default(),
+                    timestamp: None,
+                };
+
+                store.push(line, &[0]).unwrap()
+            })
+            .collect();
+        let labels: Vec<String> = labels.iter().map(|label| label.to_string()).collect();
+        let mut window = WindowState::new(Style::default());
+
+        window.display_lines(
+            &ids,
+            &store,
+            Rect::new(0, 0, 80, 20),
+            &SourcesState::new(&labels),
+            config,
+            Some(&Regex::new(highlight).unwrap()),
+        );
+
+        window
+            .lines
+            .iter()
+            .zip(&window.highlights)
+            .map(|(line, ranges)| match line {
+                Text::Raw(text) | Text::Styled(text, _) => (
+                    text.to_string(),
+                    ranges
+                        .iter()
+                        .map(|range| text[range.clone()].to_string())
+                        .collect(),
+                ),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn highlights_match_the_stored_text_after_the_source_label() {
+        let shown = shown_highlights(
+            &[(0, "^disk full"), (1, "app started")],
+            &["app.log", "db.log"],
+            &config(),
+            "^app|log",
+        );
+
+        // Newest first, and the labels neither match nor shift the anchored match
+        assert_eq!(
+            shown[0],
+            ("[db.log] app started".to_string(), vec!["app".to_string()])
+        );
+        assert_eq!(shown[1], ("[app.log] ^disk full".to_string(), vec![]));
+    }
+
+    #[test]
+    fn highlights_leave_out_the_hidden_lines_of_a_collapsed_entry() {
+        let shown = shown_highlights(
+            &[(0, "error here\nmore error")],
+            &["app.log"],
+            &config(),
+            "error|line",
+        );
+
+        assert_eq!(
+            shown[0],
+            (
+                "error here [+1 lines]".to_string(),
+                vec!["error".to_string()]
+            )
+        );
+    }
+
+    #[test]
+    fn highlights_stay_within_the_cells_of_json_columns() {
+        let mut config = config();
+        config.parser = LineParser::Json;
+        config.json_columns = vec!["level".to_string(), "msg".to_string()];
+
+        let shown = shown_highlights(
+            &[(0, r#"{"level":"info","msg":"in f","extra":1}"#)],
+            &["app.log"],
+            &config,
+            r"f|\+1|  ",
+        );
+
+        assert_eq!(shown[0].0, "INFO  in f  {+1}");
+        assert_eq!(shown[0].1, vec!["f".to_string()]);
+    }
 }
@@ -0,0 +1,757 @@
+use crate::parser::{parse_timestamp, LineParser};
+use crate::reader::decode_entry;
+use chrono::NaiveDateTime;
+use failure::Error;
+use memmap2::Mmap;
+use regex::Regex;
+use std::borrow::Cow;
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::convert::TryFrom;
+use std::fs::File;
+use std::io::Write;
+use std::os::unix::fs::FileExt;
+use std::sync::Arc;
+use tui::style::Style;
+
+pub type LineId = usize;
+
+const TAB_COMPACTION_THRESHOLD: usize = 4096;
+const SLOT_COMPACTION_THRESHOLD: usize = 4096;
+// Ids start in the middle of the range so lines read from before where a file was opened
+// can get lower ones than the lines captured so far
+const FIRST_LINE_ID: LineId = LineId::MAX / 2;
+
+/// A captured line with the style and tabs it got from its parsed record. The timestamp is
+/// kept for jumping to a time across all lines; the other parts of the record only matter
+/// when a tab is added or a single line is inspected, which parse the line again.
+#[derive(Clone)]
+pub struct StoredLine {
+    pub source: usize,
+    pub text: String,
+    pub style: Style,
+    pub timestamp: Option<NaiveDateTime>,
+}
+
+/// Limits on how many lines, and how many bytes of them, each tab keeps in memory. Without
+/// spilling, lines past the limits are forgotten; with it they are moved to a temporary
+/// file and read back when scrolled to.
+#[derive(Clone, Copy, Default)]
+pub struct RetentionLimits {
+    pub max_lines: Option<usize>,
+    pub max_bytes: Option<usize>,
+    pub spill_to_disk: bool,
+}
+
+// Lines read in place can be counted in millions, so every slot is kept to 16 bytes
+enum Slot {
+    Resident {
+        line: Box<StoredLine>,
+        tab_count: u32,
+    },
+    Spilled(Box<SpilledLine>),
+    Mapped {
+        source: u32,
+        entry: u32,
+    },
+    Dropped,
+}
+
+struct SpilledLine {
+    source: usize,
+    style: Style,
+    timestamp: Option<NaiveDateTime>,
+    offset: u64,
+    length: usize,
+}
+
+/// The entries of a file read in place, as the offsets where they start, followed by where
+/// the last one ends, and their styles as indexes into a palette. Their timestamps are not
+/// kept but parsed again, as only the lines shown or jumped across are ever read.
+struct MappedFile {
+    map: Arc<Mmap>,
+    parser: LineParser,
+    offsets: Vec<u64>,
+    styles: Vec<u16>,
+    palette: Vec<Style>,
+}
+
+struct TabIndex {
+    ids: Vec<LineId>,
+    start: usize,
+    resident_start: usize,
+    resident_bytes: usize,
+    mapped_count: usize,
+    captured_count: usize,
+}
+
+/// Keeps every captured line once, while tabs only hold the ids of the lines they show.
+/// Lines older than the first slot that some tab still holds are kept in `detached`.
+pub struct MessageStore {
+    slots: VecDeque<Slot>,
+    first_id: LineId,
+    dropped_count: usize,
+    detached: BTreeMap<LineId, Slot>,
+    has_evicted: bool,
+    tabs: Vec<TabIndex>,
+    limits: RetentionLimits,
+    spill_file: Option<File>,
+    spill_length: u64,
+    mapped_files: HashMap<usize, MappedFile>,
+}
+
+impl TabIndex {
+    fn new() -> Self {
+        Self {
+            ids: vec![],
+            start: 0,
+            resident_start: 0,
+            resident_bytes: 0,
+            mapped_count: 0,
+            captured_count: 0,
+        }
+    }
+
+    fn resident_count(&self) -> usize {
+        self.ids.len() - self.resident_start
+    }
+
+    /// Counts the resident lines the retention limits apply to, as mapped ones take no
+    /// memory.
+    fn held_count(&self) -> usize {
+        self.resident_count() - self.mapped_count
+    }
+
+    /// Takes the oldest resident line out of the tab once it is forgotten. Without spilling,
+    /// the lines before it that are still shown can only be mapped ones, which stay whatever
+    /// the limits, so the shorter side of it is moved over.
+    fn forget_oldest_resident(&mut self) {
+        let index = self.resident_start;
+
+        if index - self.start < self.ids.len() - index {
+            self.ids.copy_within(self.start..index, self.start + 1);
+            self.start += 1;
+            self.resident_start += 1;
+        } else {
+            self.ids.remove(index);
+        }
+    }
+
+    fn compact(&mut self) {
+        if self.start > TAB_COMPACTION_THRESHOLD && self.start > self.ids.len() / 2 {
+            self.ids.drain(..self.start);
+            self.resident_start -= self.start;
+            self.start = 0;
+        }
+    }
+}
+
+impl MessageStore {
+    pub fn new(tab_count: usize, limits: RetentionLimits) -> Result<MessageStore, Error> {
+        let spill_file = if limits.spill_to_disk {
+            Some(tempfile::tempfile()?)
+        } else {
+            None
+        };
+
+        Ok(MessageStore {
+            slots: VecDeque::new(),
+            first_id: FIRST_LINE_ID,
+            dropped_count: 0,
+            detached: BTreeMap::new(),
+            has_evicted: false,
+            tabs: (0..tab_count).map(|_| TabIndex::new()).collect(),
+            limits,
+            spill_file,
+            spill_length: 0,
+            mapped_files: HashMap::new(),
+        })
+    }
+
+    /// Stores a line and adds it to the given tabs, then applies the retention limits of
+    /// those tabs.
+    pub fn push(&mut self, line: StoredLine, tabs: &[usize]) -> Result<LineId, Error> {
+        let length = line.text.len();
+
+        self.push_slot(
+            Slot::Resident {
+                line: Box::new(line),
+                tab_count: tabs.len() as u32,
+            },
+            length,
+            tabs,
+        )
+    }
+
+    /// Lets lines of the source be stored as entries of its memory mapped file, parsed with
+    /// the parser when they are read.
+    pub fn add_map(&mut self, source: usize, map: Arc<Mmap>, parser: LineParser) {
+        self.mapped_files.insert(
+            source,
+            MappedFile {
+                map,
+                parser,
+                offsets: vec![0],
+                styles: vec![],
+                palette: vec![],
+            },
+        );
+    }
+
+    /// Stores the next entry of the mapped file of its source, which ends where given. It
+    /// takes no memory beyond its offset, so it is never spilled or dropped, and does not
+    /// count towards the retention limits.
+    pub fn push_mapped(
+        &mut self,
+        source: usize,
+        end: u64,
+        style: Style,
+        tabs: &[usize],
+    ) -> Result<LineId, Error> {
+        let mapped_file = match self.mapped_files.get_mut(&source) {
+            Some(mapped_file) => mapped_file,
+            None => failure::bail!("Source {} is not read in place", source),
+        };
+        let entry = u32::try_from(mapped_file.styles.len())?;
+        let style_index = match mapped_file.palette.iter().position(|known| *known == style) {
+            Some(style_index) => style_index,
+            None => {
+                mapped_file.palette.push(style);
+                mapped_file.palette.len() - 1
+            }
+        };
+
+        mapped_file.offsets.push(end);
+        mapped_file.styles.push(u16::try_from(style_index)?);
+
+        self.push_slot(
+            Slot::Mapped {
+                source: u32::try_from(source)?,
+                entry,
+            },
+            0,
+            tabs,
+        )
+    }
+
+    fn push_slot(&mut self, slot: Slot, length: usize, tabs: &[usize]) -> Result<LineId, Error> {
+        let id = self.first_id + self.slots.len();
+        let is_mapped = matches!(slot, Slot::Mapped { .. });
+
+        self.slots.push_back(slot);
+
+        for &tab in tabs {
+            self.tabs[tab].ids.push(id);
+            self.tabs[tab].resident_bytes += length;
+            self.tabs[tab].mapped_count += is_mapped as usize;
+            self.tabs[tab].captured_count += 1;
+            self.apply_limits(tab)?;
+        }
+
+        Ok(id)
+    }
+
+    /// Tells whether older lines can be added in front, which is only while no tab has let
+    /// go of a line yet, as they would otherwise be older than forgotten ones.
+    pub fn accepts_older_lines(&self) -> bool {
+        !self.has_evicted
+    }
+
+    /// Stores lines older than every captured one, given oldest first with the tabs each
+    /// goes to, then applies the retention limits of the tabs. They were in the files all
+    /// along, so they do not count as captured.
+    pub fn prepend(&mut self, lines: Vec<(StoredLine, Vec<usize>)>) -> Result<(), Error> {
+        if !self.accepts_older_lines() || lines.len() > self.first_id {
+            failure::bail!("Older lines cannot be added");
+        }
+
+        self.first_id -= lines.len();
+
+        let mut tab_ids = vec![vec![]; self.tabs.len()];
+
+        for (offset, (line, tabs)) in lines.into_iter().enumerate().rev() {
+            for &tab in &tabs {
+                tab_ids[tab].push(self.first_id + offset);
+                self.tabs[tab].resident_bytes += line.text.len();
+            }
+
+            self.slots.push_front(Slot::Resident {
+                line: Box::new(line),
+                tab_count: tabs.len() as u32,
+            });
+        }
+
+        for (tab, mut ids) in tab_ids.into_iter().enumerate() {
+            ids.reverse();
+            self.tabs[tab].ids.splice(..0, ids);
+            self.apply_limits(tab)?;
+        }
+
+        Ok(())
+    }
+
+    /// Adds a tab holding the retained lines that match, newest last. Only the trailing run
+    /// of lines still in memory is kept resident for it, older matches stay on disk.
+    pub fn add_tab<F>(&mut self, is_match: F) -> Result<(), Error>
+    where
+        F: Fn(&StoredLine) -> bool,
+    {
+        let mut tab = TabIndex::new();
+        let mut is_resident = vec![];
+        let ids = self
+            .detached
+            .keys()
+            .cloned()
+            .chain(self.first_id..self.first_id + self.slots.len());
+
+        for id in ids {
+            let slot = match self.slot(id) {
+                Some(slot) => slot,
+                None => continue,
+            };
+
+            match self.get(id) {
+                Some(line) if is_match(&line) => {
+                    tab.ids.push(id);
+                    is_resident.push(matches!(slot, Slot::Resident { .. } | Slot::Mapped { .. }));
+                }
+                _ => {}
+            }
+        }
+
+        let resident_count = is_resident.iter().rev().take_while(|is| **is).count();
+        tab.resident_start = tab.ids.len() - resident_count;
+
+        if self.spill_file.is_none() {
+            tab.start = tab.resident_start;
+        }
+
+        for &id in &tab.ids[tab.resident_start..] {
+            match self.slot_mut(id) {
+                Some(Slot::Resident { line, tab_count }) => {
+                    *tab_count += 1;
+                    tab.resident_bytes += line.text.len();
+                }
+                Some(Slot::Mapped { .. }) => tab.mapped_count += 1,
+                _ => {}
+            }
+        }
+
+        tab.captured_count = tab.ids.len() - tab.start;
+
+        self.tabs.push(tab);
+        self.apply_limits(self.tabs.len() - 1)
+    }
+
+    /// Removes a tab, spilling or dropping the lines no other tab keeps in memory.
+    pub fn remove_tab(&mut self, tab: usize) -> Result<(), Error> {
+        let tab = self.tabs.remove(tab);
+
+        for &id in &tab.ids[tab.resident_start..] {
+            self.release(id)?;
+        }
+
+        Ok(())
+    }
+
+    pub fn swap_tabs(&mut self, first: usize, second: usize) {
+        self.tabs.swap(first, second);
+    }
+
+    /// Counts every line the tab got while reading forward, including those past the
+    /// retention limits, so lines arriving can be told apart from older ones loaded in front.
+    pub fn captured_count(&self, tab: usize) -> usize {
+        self.tabs[tab].captured_count
+    }
+
+    pub fn tab_ids(&self, tab: usize) -> &[LineId] {
+        let tab = &self.tabs[tab];
+
+        &tab.ids[tab.start..]
+    }
+
+    pub fn get(&self, id: LineId) -> Option<Cow<'_, StoredLine>> {
+        match self.slot(id)? {
+            Slot::Resident { line, .. } => Some(Cow::Borrowed(line)),
+            Slot::Spilled(spilled) => {
+                let mut buffer = vec![0; spilled.length];
+
+                self.spill_file
+                    .as_ref()?
+                    .read_exact_at(&mut buffer, spilled.offset)
+                    .ok()?;
+
+                Some(Cow::Owned(StoredLine {
+                    source: spilled.source,
+                    text: String::from_utf8_lossy(&buffer).to_string(),
+                    style: spilled.style,
+                    timestamp: spilled.timestamp,
+                }))
+            }
+            Slot::Mapped { source, entry } => {
+                let mapped_file = self.mapped_files.get(&(*source as usize))?;
+                let text = self.mapped_text(*source as usize, *entry as usize)?;
+                let timestamp = mapped_file
+                    .parser
+                    .parse(&text)
+                    .timestamp
+                    .and_then(|timestamp| parse_timestamp(&timestamp));
+
+                Some(Cow::Owned(StoredLine {
+                    source: *source as usize,
+                    text: text.into_owned(),
+                    style: mapped_file.palette[mapped_file.styles[*entry as usize] as usize],
+                    timestamp,
+                }))
+            }
+            Slot::Dropped => None,
+        }
+    }
+
+    /// Gives the text of an entry read in place, borrowed from the mapped file when it can be.
+    fn mapped_text(&self, source: usize, entry: usize) -> Option<Cow<'_, str>> {
+        let mapped_file = self.mapped_files.get(&source)?;
+        let start = *mapped_file.offsets.get(entry)? as usize;
+        let end = *mapped_file.offsets.get(entry + 1)? as usize;
+
+        Some(decode_entry(mapped_file.map.get(start..end)?))
+    }
+
+    /// Tells whether the line matches, searching lines read in place without copying them.
+    pub fn is_match(&self, id: LineId, matcher: &Regex) -> bool {
+        match self.slot(id) {
+            Some(Slot::Mapped { source, entry }) => {
+                match self.mapped_text(*source as usize, *entry as usize) {
+                    Some(text) => matcher.is_match(&text),
+                    None => false,
+                }
+            }
+            _ => match self.get(id) {
+                Some(line) => matcher.is_match(&line.text),
+                None => false,
+            },
+        }
+    }
+
+    pub fn source(&self, id: LineId) -> Option<usize> {
+        match self.slot(id)? {
+            Slot::Resident { line, .. } => Some(line.source),
+            Slot::Spilled(spilled) => Some(spilled.source),
+            Slot::Mapped { source, .. } => Some(*source as usize),
+            Slot::Dropped => None,
+        }
+    }
+
+    fn slot(&self, id: LineId) -> Option<&Slot> {
+        if id < self.first_id {
+            return self.detached.get(&id);
+        }
+
+        self.slots.get(id - self.first_id)
+    }
+
+    fn slot_mut(&mut self, id: LineId) -> Option<&mut Slot> {
+        if id < self.first_id {
+            return self.detached.get_mut(&id);
+        }
+
+        self.slots.get_mut(id - self.first_id)
+    }
+
+    fn apply_limits(&mut self, tab: usize) -> Result<(), Error> {
+        while self.tabs[tab].resident_count() > 0 {
+            let (held_count, resident_bytes, oldest_resident_id) = {
+                let tab = &self.tabs[tab];
+
+                (
+                    tab.held_count(),
+                    tab.resident_bytes,
+                    tab.ids[tab.resident_start],
+                )
+            };
+
+            let exceeds_lines = match self.limits.max_lines {
+                Some(max_lines) => held_count > max_lines,
+                None => false,
+            };
+            let exceeds_bytes = match self.limits.max_bytes {
+                Some(max_bytes) => resident_bytes > max_bytes,
+                None => false,
+            };
+
+            if held_count <= 1 || !(exceeds_lines || exceeds_bytes) {
+                break;
+            }
+
+            let (length, is_mapped) = match self.slot(oldest_resident_id) {
+                Some(Slot::Resident { line, .. }) => (line.text.len(), false),
+                Some(Slot::Mapped { .. }) => (0, true),
+                _ => (0, false),
+            };
+
+            let tab = &mut self.tabs[tab];
+
+            // Mapped lines take no memory, so they stop counting as resident but stay shown
+            if is_mapped {
+                tab.resident_start += 1;
+                tab.mapped_count -= 1;
+
+                continue;
+            }
+
+            tab.resident_bytes -= length;
+
+            if self.spill_file.is_some() {
+                tab.resident_start += 1;
+            } else {
+                tab.forget_oldest_resident();
+            }
+
+            self.has_evicted = true;
+            self.release(oldest_resident_id)?;
+        }
+
+        self.tabs[tab].compact();
+
+        Ok(())
+    }
+
+    /// Called when a tab no longer keeps the line in memory, the last such tab spills or
+    /// drops it.
+    fn release(&mut self, id: LineId) -> Result<(), Error> {
+        let line = match self.slot_mut(id) {
+            Some(Slot::Resident { tab_count, .. }) if *tab_count > 1 => {
+                *tab_count -= 1;
+                return Ok(());
+            }
+            Some(Slot::Mapped { .. }) | None => return Ok(()),
+            Some(slot) => std::mem::replace(slot, Slot::Dropped),
+        };
+
+        let spilled = match (line, self.spill_file.as_mut()) {
+            (Slot::Resident { line, .. }, Some(spill_file)) => {
+                spill_file.write_all(line.text.as_bytes())?;
+                self.spill_length += line.text.len() as u64;
+
+                Slot::Spilled(Box::new(SpilledLine {
+                    source: line.source,
+                    style: line.style,
+                    timestamp: line.timestamp,
+                    offset: self.spill_length - line.text.len() as u64,
+                    length: line.text.len(),
+                }))
+            }
+            _ => Slot::Dropped,
+        };
+
+        match (id < self.first_id, spilled) {
+            (true, Slot::Dropped) => {
+                self.detached.remove(&id);
+            }
+            (false, Slot::Dropped) => self.dropped_count += 1,
+            (_, spilled) => {
+                if let Some(slot) = self.slot_mut(id) {
+                    *slot = spilled;
+                }
+            }
+        }
+
+        self.compact_slots();
+
+        Ok(())
+    }
+
+    /// Removes the dropped slots at the front. A line a quiet tab still holds would keep
+    /// every newer slot from being removed, so once most slots are dropped the lines at the
+    /// front are detached instead.
+    fn compact_slots(&mut self) {
+        loop {
+            match self.slots.front() {
+                Some(Slot::Dropped) => {
+                    self.slots.pop_front();
+                    self.dropped_count -= 1;
+                }
+                Some(_)
+                    if self.slots.len() > SLOT_COMPACTION_THRESHOLD
+                        && self.dropped_count * 2 > self.slots.len() =>
+                {
+                    if let Some(slot) = self.slots.pop_front() {
+                        self.detached.insert(self.first_id, slot);
+                    }
+                }
+                _ => return,
+            }
+
+            self.first_id += 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn line(text: &str) -> StoredLine {
+        StoredLine {
+            source: 0,
+            text: text.to_string(),
+            style: Style::default(),
+            timestamp: None,
+        }
+    }
+
+    #[test]
+    fn slots_stay_bounded_while_a_quiet_tab_holds_an_old_line() {
+        let limits = RetentionLimits {
+            max_lines: Some(10),
+            ..RetentionLimits::default()
+        };
+        let mut store = MessageStore::new(2, limits).unwrap();
+
+        store.push(line("quiet"), &[1]).unwrap();
+
+        for index in 0..100_000 {
+            store.push(line(&index.to_string()), &[0]).unwrap();
+            assert!(store.slots.len() <= SLOT_COMPACTION_THRESHOLD * 2);
+        }
+
+        let quiet_id = store.tab_ids(1)[0];
+        assert_eq!(store.get(quiet_id).unwrap().text, "quiet");
+
+        let last_id = *store.tab_ids(0).last().unwrap();
+        assert_eq!(store.get(last_id).unwrap().text, "99999");
+        assert_eq!(store.tab_ids(0).len(), 10);
+    }
+
+    #[test]
+    fn older_lines_are_refused_once_a_line_was_evicted() {
+        let limits = RetentionLimits {
+            max_lines: Some(2),
+            ..RetentionLimits::default()
+        };
+        let mut store = MessageStore::new(1, limits).unwrap();
+
+        store.push(line("a"), &[0]).unwrap();
+        store.push(line("b"), &[0]).unwrap();
+        assert!(store.accepts_older_lines());
+
+        for _ in 0..TAB_COMPACTION_THRESHOLD * 3 {
+            store.push(line("c"), &[0]).unwrap();
+        }
+
+        assert!(!store.accepts_older_lines());
+        assert!(store.prepend(vec![(line("older"), vec![0])]).is_err());
+    }
+
+    #[test]
+    fn limits_apply_to_lines_kept_in_memory_only() {
+        let limits = RetentionLimits {
+            max_lines: Some(2),
+            ..RetentionLimits::default()
+        };
+        let mut store = MessageStore::new(1, limits).unwrap();
+        let mut file = tempfile::tempfile().unwrap();
+        file.write_all(b"m0\nm1\r\nm2\nm3").unwrap();
+        let map = Arc::new(unsafe { Mmap::map(&file) }.unwrap());
+        store.add_map(0, map, LineParser::default());
+
+        let push_mapped = |store: &mut MessageStore, index: u64| {
+            let end = [3, 7, 10, 12][index as usize];
+            store.push_mapped(0, end, Style::default(), &[0]).unwrap();
+        };
+
+        // Forgetting "a" moves the mapped line before it, forgetting "b" the lines after it
+        push_mapped(&mut store, 0);
+        store.push(line("a"), &[0]).unwrap();
+        push_mapped(&mut store, 1);
+        push_mapped(&mut store, 2);
+        push_mapped(&mut store, 3);
+
+        for text in &["b", "c", "d"] {
+            store.push(line(text), &[0]).unwrap();
+        }
+
+        assert_eq!(texts(&store, 0), vec!["m0", "m1", "m2", "m3", "c", "d"]);
+        assert_eq!(std::mem::size_of::<Slot>(), 16);
+        assert_eq!(store.tab_ids(0).len(), 6);
+        assert!(!store.accepts_older_lines());
+    }
+
+    fn texts(store: &MessageStore, tab: usize) -> Vec<String> {
+        store
+            .tab_ids(tab)
+            .iter()
+            .filter_map(|id| store.get(*id).map(|line| line.text.clone()))
+            .collect()
+    }
+
+    #[test]
+    fn byte_limit_drops_the_oldest_lines_but_keeps_the_newest() {
+        let limits = RetentionLimits {
+            max_bytes: Some(5),
+            ..RetentionLimits::default()
+        };
+        let mut store = MessageStore::new(1, limits).unwrap();
+
+        for text in &["aa", "bb", "cc", "a much longer line"] {
+            store.push(line(text), &[0]).unwrap();
+        }
+
+        assert_eq!(texts(&store, 0), vec!["a much longer line"]);
+        assert_eq!(store.captured_count(0), 4);
+    }
+
+    #[test]
+    fn spilled_lines_are_read_back() {
+        let limits = RetentionLimits {
+            max_lines: Some(1),
+            spill_to_disk: true,
+            ..RetentionLimits::default()
+        };
+        let mut store = MessageStore::new(2, limits).unwrap();
+
+        store.push(line("first"), &[0, 1]).unwrap();
+        store.push(line("second"), &[0]).unwrap();
+        store.push(line("third"), &[0, 1]).unwrap();
+
+        assert_eq!(texts(&store, 0), vec!["first", "second", "third"]);
+        assert_eq!(texts(&store, 1), vec!["first", "third"]);
+    }
+
+    #[test]
+    fn prepended_lines_come_before_captured_ones_in_order() {
+        let mut store = MessageStore::new(2, RetentionLimits::default()).unwrap();
+
+        store.push(line("new"), &[0, 1]).unwrap();
+        store
+            .prepend(vec![(line("oldest"), vec![0]), (line("older"), vec![0, 1])])
+            .unwrap();
+        store.prepend(vec![(line("ancient"), vec![0])]).unwrap();
+
+        assert_eq!(texts(&store, 0), vec!["ancient", "oldest", "older", "new"]);
+        assert_eq!(texts(&store, 1), vec!["older", "new"]);
+
+        let ids = store.tab_ids(0);
+        assert!(ids.windows(2).all(|pair| pair[0] < pair[1]));
+
+        // Only lines read forward count as captured, so older ones do not show as unread
+        assert_eq!(store.captured_count(0), 1);
+        assert_eq!(store.captured_count(1), 1);
+    }
+
+    #[test]
+    fn added_tab_keeps_the_retained_matches() {
+        let limits = RetentionLimits {
+            max_lines: Some(2),
+            ..RetentionLimits::default()
+        };
+        let mut store = MessageStore::new(1, limits).unwrap();
+
+        for text in &["x1", "y1", "x2", "x3"] {
+            store.push(line(text), &[0]).unwrap();
+        }
+
+        store.add_tab(|line| line.text.starts_with('x')).unwrap();
+
+        assert_eq!(texts(&store, 1), vec!["x2", "x3"]);
+    }
+}
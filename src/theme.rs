@@ -0,0 +1,261 @@
+use crate::parser::Level;
+use failure::Error;
+use toml::Value;
+use tui::style::{Color, Modifier, Style};
+
+const FOREGROUND_TOML_PROPERTY: &str = "fg";
+const BACKGROUND_TOML_PROPERTY: &str = "bg";
+const MODIFIER_TOML_PROPERTIES: [(&str, Modifier); 5] = [
+    ("bold", Modifier::BOLD),
+    ("dim", Modifier::DIM),
+    ("italic", Modifier::ITALIC),
+    ("underline", Modifier::UNDERLINED),
+    ("reversed", Modifier::REVERSED),
+];
+
+/// Styles of the UI elements, each configurable as a table under `[theme]` in config.toml
+/// with the same `fg`, `bg` and modifier keys as filters. Messages with a level use the
+/// style of that level when one is set and `message` otherwise.
+#[derive(Clone)]
+pub struct Theme {
+    pub background: Style,
+    pub tab_bar: Style,
+    pub tab_highlight: Style,
+    pub tab_unread: Style,
+    pub tab_activity: Style,
+    pub border: Style,
+    pub selection: Style,
+    pub search: Style,
+    pub search_error: Style,
+    pub search_progress: Style,
+    pub message: Style,
+    pub marker: Style,
+    pub highlight: Style,
+    pub trace: Option<Style>,
+    pub debug: Option<Style>,
+    pub info: Option<Style>,
+    pub warn: Option<Style>,
+    pub error: Option<Style>,
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Self {
+            background: Style::default().bg(Color::White),
+            tab_bar: Style::default().fg(Color::Cyan),
+            tab_highlight: Style::default().fg(Color::Yellow),
+            tab_unread: Style::default().fg(Color::LightGreen),
+            tab_activity: Style::default().fg(Color::Black).bg(Color::LightGreen),
+            border: Style::default(),
+            selection: Style::default().modifier(Modifier::REVERSED),
+            search: Style::default(),
+            search_error: Style::default().fg(Color::LightRed),
+            search_progress: Style::default().fg(Color::DarkGray),
+            message: Style::default().fg(Color::White),
+            marker: Style::default().fg(Color::Black).bg(Color::White),
+            highlight: Style::default().fg(Color::Black).bg(Color::Yellow),
+            trace: None,
+            debug: None,
+            info: None,
+            warn: Some(Style::default().fg(Color::Yellow)),
+            error: Some(Style::default().fg(Color::LightRed)),
+        }
+    }
+}
+
+impl Theme {
+    pub fn new(theme: Option<&Value>) -> Result<Theme, Error> {
+        let mut result = Theme::default();
+
+        let theme = match theme {
+            Some(Value::Table(theme)) => theme,
+            Some(_) => failure::bail!("theme must be a table"),
+            None => return Ok(result),
+        };
+
+        for (element, value) in theme {
+            let level_style = match element.as_str() {
+                "trace" => Some(&mut result.trace),
+                "debug" => Some(&mut result.debug),
+                "info" => Some(&mut result.info),
+                "warn" => Some(&mut result.warn),
+                "error" => Some(&mut result.error),
+                _ => None,
+            };
+
+            if let Some(level_style) = level_style {
+                *level_style = Some(
+                    parse_style(value, Style::default())
+                        .map_err(|e| failure::format_err!("theme.{}: {}", element, e))?,
+                );
+
+                continue;
+            }
+
+            let style = match element.as_str() {
+                "background" => &mut result.background,
+                "tab_bar" => &mut result.tab_bar,
+                "tab_highlight" => &mut result.tab_highlight,
+                "tab_unread" => &mut result.tab_unread,
+                "tab_activity" => &mut result.tab_activity,
+                "border" => &mut result.border,
+                "selection" => &mut result.selection,
+                "search" => &mut result.search,
+                "search_error" => &mut result.search_error,
+                "search_progress" => &mut result.search_progress,
+                "message" => &mut result.message,
+                "marker" => &mut result.marker,
+                "highlight" => &mut result.highlight,
+                _ => failure::bail!(
+                    "unknown theme element {}, expected background, tab_bar, tab_highlight, \
+                     tab_unread, tab_activity, border, selection, search, search_error, \
+                     search_progress, message, marker, highlight, trace, debug, info, warn or error",
+                    element
+                ),
+            };
+
+            *style = parse_style(value, Style::default())
+                .map_err(|e| failure::format_err!("theme.{}: {}", element, e))?;
+        }
+
+        Ok(result)
+    }
+
+    pub fn message_style(&self, level: Option<Level>) -> Style {
+        let level_style = match level {
+            Some(Level::Trace) => self.trace,
+            Some(Level::Debug) => self.debug,
+            Some(Level::Info) => self.info,
+            Some(Level::Warn) => self.warn,
+            Some(Level::Error) | Some(Level::Fatal) => self.error,
+            None => None,
+        };
+
+        level_style.unwrap_or(self.message)
+    }
+}
+
+/// Reads `fg`, `bg` and modifier flags from a config table, keeping `default` for
+/// whatever the table leaves out.
+pub fn parse_style(value: &Value, default: Style) -> Result<Style, Error> {
+    let mut style = default;
+
+    if let Some(color) = value.get(FOREGROUND_TOML_PROPERTY) {
+        style = style.fg(parse_color(color)?);
+    }
+
+    if let Some(color) = value.get(BACKGROUND_TOML_PROPERTY) {
+        style = style.bg(parse_color(color)?);
+    }
+
+    for (property, modifier) in MODIFIER_TOML_PROPERTIES.iter() {
+        match value.get(property) {
+            Some(Value::Boolean(true)) => style = style.modifier(style.modifier | *modifier),
+            Some(Value::Boolean(false)) => style = style.modifier(style.modifier - *modifier),
+            Some(_) => failure::bail!("{} must be true or false", property),
+            None => {}
+        }
+    }
+
+    Ok(style)
+}
+
+/// Accepts color names, `#rrggbb` hex values and 0-255 terminal palette indexes.
+pub fn parse_color(value: &Value) -> Result<Color, Error> {
+    let name = match value {
+        Value::Integer(index) if *index >= 0 && *index <= 255 => {
+            return Ok(Color::Indexed(*index as u8))
+        }
+        Value::String(name) => name.to_lowercase().replace('_', ""),
+        _ => failure::bail!("color must be a name, #rrggbb or a number from 0 to 255"),
+    };
+
+    let color = match name.as_str() {
+        "reset" | "default" => Color::Reset,
+        "black" => Color::Black,
+        "red" => Color::Red,
+        "green" => Color::Green,
+        "yellow" => Color::Yellow,
+        "blue" => Color::Blue,
+        "magenta" => Color::Magenta,
+        "cyan" => Color::Cyan,
+        "gray" | "grey" => Color::Gray,
+        "darkgray" | "darkgrey" => Color::DarkGray,
+        "lightred" => Color::LightRed,
+        "lightgreen" => Color::LightGreen,
+        "lightyellow" => Color::LightYellow,
+        "lightblue" => Color::LightBlue,
+        "lightmagenta" => Color::LightMagenta,
+        "lightcyan" => Color::LightCyan,
+        "white" => Color::White,
+        hex if hex.starts_with('#') && hex.len() == 7 && hex.is_ascii() => {
+            let channel = |range| u8::from_str_radix(&hex[range], 16);
+
+            match (channel(1..3), channel(3..5), channel(5..7)) {
+                (Ok(red), Ok(green), Ok(blue)) => Color::Rgb(red, green, blue),
+                _ => failure::bail!("invalid hex color {}", hex),
+            }
+        }
+        index => match index.parse::<u8>() {
+            Ok(index) => Color::Indexed(index),
+            Err(_) => failure::bail!("unknown color {}", index),
+        },
+    };
+
+    Ok(color)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn color(value: &str) -> Result<Color, String> {
+        parse_color(&Value::String(value.to_string())).map_err(|e| e.to_string())
+    }
+
+    #[test]
+    fn colors_are_read_from_names_hex_values_and_indexes() {
+        assert!(color("Light_Red") == Ok(Color::LightRed));
+        assert!(color("darkgrey") == Ok(Color::DarkGray));
+        assert!(color("#ff8000") == Ok(Color::Rgb(255, 128, 0)));
+        assert!(color("42") == Ok(Color::Indexed(42)));
+        assert!(parse_color(&Value::Integer(255)).ok() == Some(Color::Indexed(255)));
+        assert!(parse_color(&Value::Integer(256)).is_err());
+        assert_eq!(color("purple"), Err("unknown color purple".to_string()));
+        assert_eq!(
+            color("#ff80zz"),
+            Err("invalid hex color #ff80zz".to_string())
+        );
+    }
+
+    #[test]
+    fn hex_colors_with_multibyte_characters_are_rejected() {
+        // Seven bytes, but slicing by byte ranges would split the `é`.
+        assert_eq!(color("#ffé00"), Err("unknown color #ffé00".to_string()));
+        assert_eq!(color("#é1234"), Err("unknown color #é1234".to_string()));
+    }
+
+    #[test]
+    fn theme_styles_override_the_defaults_per_element() {
+        let value: Value =
+            toml::from_str("[search_error]\nfg = \"magenta\"\nbold = true\n[error]\nbg = \"red\"")
+                .unwrap();
+        let theme = Theme::new(Some(&value)).unwrap();
+
+        assert!(theme.search_error == Style::default().fg(Color::Magenta).modifier(Modifier::BOLD));
+        assert!(theme.search_progress == Theme::default().search_progress);
+        assert!(theme.message_style(Some(Level::Fatal)) == Style::default().bg(Color::Red));
+        assert!(theme.message_style(None) == theme.message);
+    }
+
+    #[test]
+    fn unknown_theme_elements_and_bad_modifiers_are_rejected() {
+        let value: Value = toml::from_str("[tabs]\nfg = \"red\"").unwrap();
+        let error = Theme::new(Some(&value)).err().unwrap().to_string();
+        assert!(error.starts_with("unknown theme element tabs"), "{}", error);
+
+        let value: Value = toml::from_str("[border]\nbold = \"yes\"").unwrap();
+        let error = Theme::new(Some(&value)).err().unwrap().to_string();
+        assert_eq!(error, "theme.border: bold must be true or false");
+    }
+}
@@ -0,0 +1,207 @@
+use std::ops::Range;
+use tui::buffer::Buffer;
+use tui::layout::Rect;
+use tui::style::Style;
+use tui::widgets::{Block, Text, Widget};
+use unicode_width::UnicodeWidthChar;
+
+/// A list drawn from the bottom up like `List` with `Corner::BottomLeft`, which can also
+/// style parts of a line, given as byte ranges, to highlight search matches, and either
+/// wrap long lines over several rows or show them scrolled sideways.
+pub struct MessageList<'a, 'b> {
+    block: Option<Block<'a>>,
+    lines: &'b [Text<'b>],
+    highlights: &'b [Vec<Range<usize>>],
+    highlight_style: Style,
+    is_wrapping: bool,
+    horizontal_offset: usize,
+}
+
+impl<'a, 'b> MessageList<'a, 'b> {
+    pub fn new(lines: &'b [Text<'b>], highlights: &'b [Vec<Range<usize>>]) -> Self {
+        Self {
+            block: None,
+            lines,
+            highlights,
+            highlight_style: Style::default(),
+            is_wrapping: false,
+            horizontal_offset: 0,
+        }
+    }
+
+    pub fn block(mut self, block: Block<'a>) -> Self {
+        self.block = Some(block);
+        self
+    }
+
+    pub fn highlight_style(mut self, style: Style) -> Self {
+        self.highlight_style = style;
+        self
+    }
+
+    pub fn wrap(mut self, is_wrapping: bool) -> Self {
+        self.is_wrapping = is_wrapping;
+        self
+    }
+
+    /// Skips this many columns at the start of every line when not wrapping.
+    pub fn horizontal_offset(mut self, offset: usize) -> Self {
+        self.horizontal_offset = offset;
+        self
+    }
+
+    fn styled_chars(&self, i: usize, text: &str, style: Style) -> Vec<(char, Style)> {
+        let ranges = self.highlights.get(i).map(Vec::as_slice).unwrap_or(&[]);
+
+        text.char_indices()
+            .map(|(position, c)| {
+                if ranges.iter().any(|range| range.contains(&position)) {
+                    (c, self.highlight_style)
+                } else {
+                    (c, style)
+                }
+            })
+            .collect()
+    }
+
+    /// Splits one line into the rows it is drawn on, or a single row with the first
+    /// columns skipped when not wrapping.
+    fn rows<'c>(&self, chars: &'c [(char, Style)], width: usize) -> Vec<&'c [(char, Style)]> {
+        if self.is_wrapping {
+            let row_starts = wrap_chars(chars.iter().map(|(c, _)| *c), width);
+
+            return row_starts
+                .iter()
+                .zip(row_starts.iter().skip(1).chain(Some(&chars.len())))
+                .map(|(start, end)| &chars[*start..*end])
+                .collect();
+        }
+
+        let mut skipped_width = 0;
+        let skipped_count = chars
+            .iter()
+            .take_while(|(c, _)| {
+                skipped_width += c.width().unwrap_or(0);
+                skipped_width <= self.horizontal_offset
+            })
+            .count();
+
+        vec![&chars[skipped_count..]]
+    }
+}
+
+/// Counts the rows a line takes when wrapped at `width` columns, each of the lines of a
+/// multi-line entry starting a row of its own.
+pub fn wrapped_row_count(text: &str, width: usize) -> usize {
+    text.split('\n')
+        .map(|line| wrap_chars(line.chars(), width).len())
+        .sum()
+}
+
+/// Splits characters into rows no wider than `width`, giving the index where each row
+/// starts. Every line takes at least one row, even when empty.
+fn wrap_chars<I: Iterator<Item = char>>(chars: I, width: usize) -> Vec<usize> {
+    let mut row_starts = vec![0];
+    let mut row_width = 0;
+
+    for (index, c) in chars.enumerate() {
+        let char_width = c.width().unwrap_or(0);
+
+        if row_width + char_width > width && row_width > 0 {
+            row_starts.push(index);
+            row_width = 0;
+        }
+
+        row_width += char_width;
+    }
+
+    row_starts
+}
+
+impl<'a, 'b> Widget for MessageList<'a, 'b> {
+    fn draw(&mut self, area: Rect, buf: &mut Buffer) {
+        let list_area = match self.block {
+            Some(ref mut block) => {
+                block.draw(area, buf);
+                block.inner(area)
+            }
+            None => area,
+        };
+
+        if list_area.width < 1 || list_area.height < 1 {
+            return;
+        }
+
+        let width = list_area.width as usize;
+        let mut bottom = list_area.bottom();
+
+        for (i, line) in self.lines.iter().enumerate() {
+            if bottom <= list_area.top() {
+                break;
+            }
+
+            let (text, style) = match line {
+                Text::Raw(text) => (text, Style::default()),
+                Text::Styled(text, style) => (text, *style),
+            };
+            let chars = self.styled_chars(i, text, style);
+
+            let rows: Vec<&[(char, Style)]> = chars
+                .split(|(c, _)| *c == '\n')
+                .flat_map(|chars| self.rows(chars, width))
+                .collect();
+
+            // The oldest line shown may not fit whole, then only its last rows are drawn
+            let top = i32::from(bottom) - rows.len() as i32;
+
+            for (row_index, row) in rows.iter().enumerate() {
+                let y = top + row_index as i32;
+
+                if y < i32::from(list_area.top()) {
+                    continue;
+                }
+
+                let y = y as u16;
+
+                let mut x = list_area.left();
+
+                for (c, char_style) in row.iter() {
+                    let char_width = c.width().unwrap_or(0) as u16;
+
+                    if x + char_width > list_area.right() {
+                        break;
+                    }
+
+                    buf.set_stringn(
+                        x,
+                        y,
+                        c.encode_utf8(&mut [0; 4]),
+                        char_width as usize,
+                        *char_style,
+                    );
+                    x += char_width;
+                }
+            }
+
+            bottom = top.max(i32::from(list_area.top())) as u16;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rows_break_before_the_char_that_does_not_fit() {
+        assert_eq!(wrap_chars("abcdefg".chars(), 3), vec![0, 3, 6]);
+        assert_eq!(wrap_chars("abcdef".chars(), 3), vec![0, 3]);
+        assert_eq!(wrap_chars("".chars(), 3), vec![0]);
+    }
+
+    #[test]
+    fn wide_chars_are_not_split_across_rows() {
+        assert_eq!(wrap_chars("a日本".chars(), 4), vec![0, 2]);
+        assert_eq!(wrap_chars("日本".chars(), 1), vec![0, 1]);
+    }
+}
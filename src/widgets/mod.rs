@@ -0,0 +1,5 @@
+mod message_list;
+mod tab_bar;
+
+pub use self::message_list::{wrapped_row_count, MessageList};
+pub use self::tab_bar::{title_ranges, TabBar};
@@ -0,0 +1,96 @@
+use std::ops::Range;
+use tui::buffer::Buffer;
+use tui::layout::Rect;
+use tui::style::Style;
+use tui::symbols::line;
+use tui::widgets::{Block, Widget};
+use unicode_width::UnicodeWidthStr;
+
+/// A tab bar like `Tabs`, except each tab is drawn in its own style so tabs with unread or
+/// new lines can stand out.
+pub struct TabBar<'a, 'b> {
+    block: Option<Block<'a>>,
+    titles: &'b [String],
+    styles: &'b [Style],
+    style: Style,
+}
+
+impl<'a, 'b> TabBar<'a, 'b> {
+    pub fn new(titles: &'b [String], styles: &'b [Style]) -> Self {
+        Self {
+            block: None,
+            titles,
+            styles,
+            style: Style::default(),
+        }
+    }
+
+    pub fn block(mut self, block: Block<'a>) -> Self {
+        self.block = Some(block);
+        self
+    }
+
+    /// The style of the bar itself and the dividers between tabs.
+    pub fn style(mut self, style: Style) -> Self {
+        self.style = style;
+        self
+    }
+}
+
+impl<'a, 'b> Widget for TabBar<'a, 'b> {
+    fn draw(&mut self, area: Rect, buf: &mut Buffer) {
+        let tabs_area = match self.block {
+            Some(ref mut block) => {
+                block.draw(area, buf);
+                block.inner(area)
+            }
+            None => area,
+        };
+
+        if tabs_area.height < 1 {
+            return;
+        }
+
+        self.background(tabs_area, buf, self.style.bg);
+
+        let title_ranges = title_ranges(self.titles, tabs_area);
+
+        for (i, (title, range)) in self.titles.iter().zip(&title_ranges).enumerate() {
+            let style = self.styles.get(i).cloned().unwrap_or(self.style);
+
+            buf.set_stringn(
+                range.start,
+                tabs_area.top(),
+                title,
+                (tabs_area.right() - range.start) as usize,
+                style,
+            );
+
+            if range.end + 1 < tabs_area.right() && i + 1 < self.titles.len() {
+                buf.set_string(range.end + 1, tabs_area.top(), line::VERTICAL, self.style);
+            }
+        }
+    }
+}
+
+/// Gives the columns each title takes within the inner area of the tab bar, for the titles
+/// that start inside it.
+pub fn title_ranges(titles: &[String], area: Rect) -> Vec<Range<u16>> {
+    let mut ranges = vec![];
+    let mut x = area.left();
+
+    for title in titles {
+        x += 1;
+
+        if x >= area.right() {
+            break;
+        }
+
+        let end = x.saturating_add(title.width() as u16).min(area.right());
+        ranges.push(x..end);
+
+        x = end.saturating_add(1 + line::VERTICAL.width() as u16);
+    }
+
+    ranges
+}